@@ -0,0 +1,173 @@
+//! Compares the commit log against baselines on identical workloads, printing a table.
+//!
+//! cargo bench --bench compare
+//!
+//! Every subject appends the same records, then scans them all back. Other log crates can be
+//! compared by implementing `Subject` for them.
+use commit_log::{CommitLog, Reader, Record};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+/// (amount of records, size of each record in bytes)
+const WORKLOADS: [(usize, usize); 3] = [(200_000, 64), (100_000, 512), (20_000, 4096)];
+
+/// Subject
+///
+/// A log implementation under comparison.
+trait Subject {
+    fn name(&self) -> &'static str;
+
+    /// Append a record
+    fn append(&mut self, record: &[u8]);
+
+    /// Make the appended records readable, e.g. flushing buffers
+    fn finish(&mut self);
+
+    /// Read every record back, returning the amount of bytes read
+    fn scan(&mut self) -> usize;
+}
+
+struct Voik {
+    clog: CommitLog,
+}
+
+impl Voik {
+    fn new(path: &Path) -> Self {
+        Self {
+            clog: CommitLog::new(path, 100_000_000, 10_000_000).unwrap(), // 100MB, 10MB
+        }
+    }
+}
+
+impl Subject for Voik {
+    fn name(&self) -> &'static str {
+        "voik"
+    }
+
+    fn append(&mut self, record: &[u8]) {
+        self.clog.write(record).unwrap();
+    }
+
+    fn finish(&mut self) {}
+
+    fn scan(&mut self) -> usize {
+        let reader = Reader::new(&self.clog);
+        let start = Record {
+            segment_index: 0,
+            current_offset: 0,
+        };
+        let deadline = Instant::now() + Duration::from_secs(3600);
+
+        reader
+            .read_budgeted(&start, usize::MAX, deadline)
+            .unwrap()
+            .bytes
+    }
+}
+
+/// AppendFile
+///
+/// The simplest possible log: length-prefixed records appended to a buffered file.
+struct AppendFile {
+    path: std::path::PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl AppendFile {
+    fn new(path: &Path) -> Self {
+        let path = path.join("append.log");
+        Self {
+            writer: Some(BufWriter::new(File::create(&path).unwrap())),
+            path,
+        }
+    }
+}
+
+impl Subject for AppendFile {
+    fn name(&self) -> &'static str {
+        "append-file"
+    }
+
+    fn append(&mut self, record: &[u8]) {
+        let writer = self.writer.as_mut().unwrap();
+        writer
+            .write_all(&(record.len() as u32).to_le_bytes())
+            .unwrap();
+        writer.write_all(record).unwrap();
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush().unwrap();
+        }
+    }
+
+    fn scan(&mut self) -> usize {
+        let mut reader = BufReader::new(File::open(&self.path).unwrap());
+        let mut bytes = 0;
+        let mut length = [0; 4];
+        let mut record = Vec::new();
+        while reader.read_exact(&mut length).is_ok() {
+            record.resize(u32::from_le_bytes(length) as usize, 0);
+            reader.read_exact(&mut record).unwrap();
+            bytes += record.len();
+        }
+        bytes
+    }
+}
+
+/// Creates a subject in the given directory
+type Constructor = fn(&Path) -> Box<dyn Subject>;
+
+fn throughput(bytes: usize, elapsed: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64()
+}
+
+fn run(subject: &mut dyn Subject, count: usize, size: usize) -> (f64, f64) {
+    let record = vec![b'x'; size];
+
+    let start = Instant::now();
+    for _ in 0..count {
+        subject.append(&record);
+    }
+    subject.finish();
+    let write = throughput(count * size, start.elapsed());
+
+    let start = Instant::now();
+    let bytes = subject.scan();
+    let read = throughput(bytes, start.elapsed());
+    assert_eq!(bytes, count * size, "{} lost records", subject.name());
+
+    (write, read)
+}
+
+fn main() {
+    println!(
+        "{:<12} {:>10} {:>8} {:>14} {:>14}",
+        "subject", "records", "size", "write MB/s", "read MB/s"
+    );
+
+    for (count, size) in WORKLOADS.iter() {
+        let subjects: Vec<Constructor> = vec![|path| Box::new(Voik::new(path)), |path| {
+            Box::new(AppendFile::new(path))
+        }];
+
+        for subject in subjects {
+            let tmp_dir = tempdir().unwrap();
+            let mut subject = subject(tmp_dir.path());
+            let (write, read) = run(subject.as_mut(), *count, *size);
+
+            println!(
+                "{:<12} {:>10} {:>8} {:>14.1} {:>14.1}",
+                subject.name(),
+                count,
+                size,
+                write,
+                read
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// Shared by all examples, not every example uses every helper.
+#![allow(dead_code)]
+
+use rand::Rng;
+use std::env;
+use std::path::PathBuf;
+
+/// Kinds of synthetic events emitted by the generator
+pub const KINDS: [&str; 4] = ["click", "view", "purchase", "signup"];
+
+pub const SEGMENT_SIZE: usize = 1_000_000; // 1MB
+pub const INDEX_SIZE: usize = 100_000; // 100KB
+
+/// Event
+///
+/// A synthetic event, rendered as JSON when written to the log.
+pub struct Event {
+    pub id: usize,
+    pub kind: &'static str,
+    pub user: usize,
+    pub value: u32,
+}
+
+impl Event {
+    /// Generate a random event with the given id
+    pub fn random(id: usize) -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            id,
+            kind: KINDS[rng.gen_range(0..KINDS.len())],
+            user: rng.gen_range(0..1_000),
+            value: rng.gen_range(0..10_000),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"id":{},"kind":"{}","user":"user-{}","value":{}}}"#,
+            self.id, self.kind, self.user, self.value
+        )
+    }
+}
+
+/// Extract the value of a string field from the flat JSON produced by `Event::to_json`
+pub fn json_str<'a>(json: &'a str, field: &str) -> Option<&'a str> {
+    let key = format!(r#""{}":""#, field);
+    let start = json.find(&key)? + key.len();
+    let end = json[start..].find('"')? + start;
+    Some(&json[start..end])
+}
+
+/// Extract the value of a numeric field from the flat JSON produced by `Event::to_json`
+pub fn json_u64(json: &str, field: &str) -> Option<u64> {
+    let key = format!(r#""{}":"#, field);
+    let start = json.find(&key)? + key.len();
+    let end = json[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(json.len(), |e| e + start);
+    json[start..end].parse().ok()
+}
+
+/// The directory given as first argument, or a default under the temp dir
+pub fn target_path(name: &str) -> PathBuf {
+    env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join(format!("voik-{}", name)))
+}
@@ -0,0 +1,50 @@
+//! Produces synthetic JSON events, then consumes them computing per-kind aggregates.
+//!
+//! cargo run --example consumer [path]
+use commit_log::{CommitLog, Error};
+use std::collections::BTreeMap;
+use std::str;
+
+mod common;
+use common::*;
+
+const TOTAL_EVENTS: usize = 10_000;
+
+#[derive(Default)]
+struct Aggregate {
+    count: u64,
+    sum: u64,
+}
+
+fn main() -> Result<(), Error> {
+    let path = target_path("consumer");
+    let mut clog = CommitLog::new(path, SEGMENT_SIZE, INDEX_SIZE)?;
+
+    for id in 0..TOTAL_EVENTS {
+        clog.write(Event::random(id).to_json().as_bytes())?;
+    }
+
+    let mut aggregates: BTreeMap<String, Aggregate> = BTreeMap::new();
+
+    for buffer in clog.iter() {
+        let json = str::from_utf8(buffer?).expect("events are valid UTF-8");
+        let kind = json_str(json, "kind").expect("events have a kind");
+        let value = json_u64(json, "value").expect("events have a value");
+
+        let aggregate = aggregates.entry(kind.to_string()).or_default();
+        aggregate.count += 1;
+        aggregate.sum += value;
+    }
+
+    println!("{:<10} {:>8} {:>12} {:>10}", "kind", "count", "sum", "avg");
+    for (kind, aggregate) in aggregates {
+        println!(
+            "{:<10} {:>8} {:>12} {:>10.2}",
+            kind,
+            aggregate.count,
+            aggregate.sum,
+            aggregate.sum as f64 / aggregate.count as f64
+        );
+    }
+    Ok(())
+}
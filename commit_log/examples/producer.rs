@@ -0,0 +1,31 @@
+//! Writes synthetic JSON events to a commit log.
+//!
+//! cargo run --example producer [path]
+use commit_log::{CommitLog, Error};
+use std::time::Instant;
+
+mod common;
+use common::*;
+
+const TOTAL_EVENTS: usize = 100_000;
+
+fn main() -> Result<(), Error> {
+    let path = target_path("producer");
+    println!("⚫️ producing {} events to {:?}", TOTAL_EVENTS, path);
+
+    let mut clog = CommitLog::new(path, SEGMENT_SIZE, INDEX_SIZE)?;
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for id in 0..TOTAL_EVENTS {
+        bytes += clog.write(Event::random(id).to_json().as_bytes())?;
+    }
+
+    println!(
+        "{} events ({} bytes) written in {:?}",
+        TOTAL_EVENTS,
+        bytes,
+        start.elapsed()
+    );
+    Ok(())
+}
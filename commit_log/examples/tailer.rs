@@ -0,0 +1,49 @@
+//! Appends batches of synthetic events and tails the log, printing every new record.
+//!
+//! cargo run --example tailer [path]
+use commit_log::{CommitLog, Error, Reader, Record};
+use std::str;
+use std::thread;
+use std::time::Duration;
+
+mod common;
+use common::*;
+
+const BATCHES: usize = 5;
+const BATCH_SIZE: usize = 3;
+
+fn main() -> Result<(), Error> {
+    let path = target_path("tailer");
+    let mut clog = CommitLog::new(path, SEGMENT_SIZE, INDEX_SIZE)?;
+
+    let mut record = Record {
+        current_offset: 0,
+        segment_index: 0,
+    };
+    let mut id = 0;
+
+    for batch in 0..BATCHES {
+        for _ in 0..BATCH_SIZE {
+            clog.write(Event::random(id).to_json().as_bytes())?;
+            id += 1;
+        }
+
+        println!("-- batch {}", batch);
+        let reader = Reader::new(&clog);
+        loop {
+            match reader.try_read(&record) {
+                Ok(Some(buffer)) => {
+                    println!("{}", str::from_utf8(buffer).unwrap_or("<binary>"));
+                    record = Reader::next(&record);
+                }
+                // caught up, wait for the next batch
+                Ok(None) => break,
+                // past the end of a sealed segment, move on to the next one
+                Err(_) => record = Reader::next_segment(&record),
+            }
+        }
+
+        thread::sleep(Duration::from_millis(200));
+    }
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use crate::format::{ANNOTATION_HEADER_SIZE as HEADER_SIZE, CRC_SIZE};
+use crate::Record;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crc::crc32;
+use derive_more::From;
+
+#[derive(Debug, From)]
+pub enum Error {
+    Io(io::Error),
+    /// The start of the range is after its end
+    InvalidRange,
+    /// The annotations were opened read-only
+    ReadOnly,
+}
+
+/// Annotation
+///
+/// Metadata attached after the fact to an inclusive range of records, e.g. "reprocessed" or
+/// "quarantined", without touching the records themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// First record of the range
+    pub from: Record,
+    /// Last record of the range
+    pub to: Record,
+    /// Metadata of the range
+    pub data: Vec<u8>,
+}
+
+impl Annotation {
+    /// Return true if the record is within the range
+    pub fn contains(&self, record: &Record) -> bool {
+        (key(&self.from)..=key(&self.to)).contains(&key(record))
+    }
+}
+
+/// Annotations
+///
+/// The annotations of a commit log, kept in memory and appended to a sidecar file:
+///
+/// annotations.log
+///
+/// Each annotation is laid out as:
+///
+/// |--------------|-------------|------------|-----------|--------|------|-------|
+/// | from segment | from offset | to segment | to offset | length | data | crc32 |
+/// |--------------|-------------|------------|-----------|--------|------|-------|
+///        8B            8B            8B          8B        4B     length   4B
+///
+/// The CRC32C covers everything before it. On open, an incomplete or corrupted tail (e.g. of
+/// a crash mid-append) is truncated.
+#[derive(Debug)]
+pub struct Annotations {
+    /// The sidecar file, `None` when opened read-only
+    file: Option<File>,
+    entries: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Open the sidecar file in the given directory, loading the existing annotations
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path.join("annotations.log"))?;
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        let (entries, valid) = decode_all(&buffer);
+
+        // drop the damaged tail, so the following appends stay readable
+        if valid < buffer.len() {
+            file.set_len(valid as u64)?;
+        }
+
+        Ok(Self {
+            file: Some(file),
+            entries,
+        })
+    }
+
+    /// Load the existing annotations of the given directory, without ever modifying the file
+    pub fn open_read_only(path: &Path) -> Result<Self, Error> {
+        let mut buffer = Vec::new();
+        match File::open(path.join("annotations.log")) {
+            Ok(mut file) => {
+                file.read_to_end(&mut buffer)?;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            file: None,
+            entries: decode_all(&buffer).0,
+        })
+    }
+
+    /// Durably append an annotation
+    pub fn append(&mut self, annotation: Annotation) -> Result<(), Error> {
+        if key(&annotation.from) > key(&annotation.to) {
+            return Err(Error::InvalidRange);
+        }
+
+        let file = self.file.as_mut().ok_or(Error::ReadOnly)?;
+        file.write_all(&encode(&annotation))?;
+        file.sync_data()?;
+        self.entries.push(annotation);
+        Ok(())
+    }
+
+    /// Return the annotations covering the record, in the order they were added
+    pub fn covering(&self, record: &Record) -> Vec<&Annotation> {
+        self.entries.iter().filter(|a| a.contains(record)).collect()
+    }
+}
+
+fn key(record: &Record) -> (usize, usize) {
+    (record.segment_index, record.current_offset)
+}
+
+fn encode(annotation: &Annotation) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(HEADER_SIZE + annotation.data.len() + CRC_SIZE);
+    for position in [
+        annotation.from.segment_index,
+        annotation.from.current_offset,
+        annotation.to.segment_index,
+        annotation.to.current_offset,
+    ]
+    .iter()
+    {
+        buffer.extend_from_slice(&(*position as u64).to_le_bytes());
+    }
+    buffer.extend_from_slice(&(annotation.data.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&annotation.data);
+    let crc = crc32::checksum_castagnoli(&buffer);
+    buffer.extend_from_slice(&crc.to_le_bytes());
+    buffer
+}
+
+/// Decode the annotations of the buffer, returning them and the amount of valid bytes
+fn decode_all(buffer: &[u8]) -> (Vec<Annotation>, usize) {
+    let mut entries = Vec::new();
+    let mut valid = 0;
+    while let Some((annotation, size)) = decode(&buffer[valid..]) {
+        entries.push(annotation);
+        valid += size;
+    }
+
+    (entries, valid)
+}
+
+/// Decode the annotation at the start of the buffer, returning it and its encoded size
+fn decode(buffer: &[u8]) -> Option<(Annotation, usize)> {
+    if buffer.len() < HEADER_SIZE + CRC_SIZE {
+        return None;
+    }
+
+    let u64_at = |at: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&buffer[at..(at + 8)]);
+        u64::from_le_bytes(bytes) as usize
+    };
+    let mut length = [0; 4];
+    length.copy_from_slice(&buffer[32..HEADER_SIZE]);
+    let length = u32::from_le_bytes(length) as usize;
+
+    let size = HEADER_SIZE + length + CRC_SIZE;
+    if buffer.len() < size {
+        return None;
+    }
+
+    let mut crc = [0; 4];
+    crc.copy_from_slice(&buffer[(HEADER_SIZE + length)..size]);
+    if crc32::checksum_castagnoli(&buffer[0..(HEADER_SIZE + length)]) != u32::from_le_bytes(crc) {
+        return None;
+    }
+
+    let annotation = Annotation {
+        from: Record {
+            segment_index: u64_at(0),
+            current_offset: u64_at(8),
+        },
+        to: Record {
+            segment_index: u64_at(16),
+            current_offset: u64_at(24),
+        },
+        data: buffer[HEADER_SIZE..(HEADER_SIZE + length)].to_vec(),
+    };
+
+    Some((annotation, size))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(segment_index: usize, current_offset: usize) -> Record {
+        Record {
+            segment_index,
+            current_offset,
+        }
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let tmp_dir = tempdir().unwrap();
+        let mut a = Annotations::open(tmp_dir.path()).unwrap();
+
+        a.append(Annotation {
+            from: record(0, 1),
+            to: record(1, 0),
+            data: b"reprocessed".to_vec(),
+        })
+        .unwrap();
+        a.append(Annotation {
+            from: record(1, 0),
+            to: record(1, 0),
+            data: b"quarantined".to_vec(),
+        })
+        .unwrap();
+
+        let data = |a: &Annotations, r: Record| -> Vec<Vec<u8>> {
+            a.covering(&r).iter().map(|a| a.data.clone()).collect()
+        };
+        assert!(data(&a, record(0, 0)).is_empty());
+        assert_eq!(data(&a, record(0, 5)), vec![b"reprocessed".to_vec()]);
+        assert_eq!(
+            data(&a, record(1, 0)),
+            vec![b"reprocessed".to_vec(), b"quarantined".to_vec()]
+        );
+
+        // a torn append is ignored on reopen
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(tmp_dir.path().join("annotations.log"))
+            .unwrap();
+        file.write_all(&encode(&a.entries[0])[0..10]).unwrap();
+
+        let mut a = Annotations::open(tmp_dir.path()).unwrap();
+        assert_eq!(data(&a, record(1, 0)).len(), 2);
+
+        // and appends after it remain readable
+        a.append(Annotation {
+            from: record(2, 0),
+            to: record(2, 0),
+            data: b"late".to_vec(),
+        })
+        .unwrap();
+        let a = Annotations::open(tmp_dir.path()).unwrap();
+        assert_eq!(data(&a, record(2, 0)), vec![b"late".to_vec()]);
+        assert_eq!(a.entries.len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_range() {
+        let tmp_dir = tempdir().unwrap();
+        let mut a = Annotations::open(tmp_dir.path()).unwrap();
+
+        let result = a.append(Annotation {
+            from: record(1, 0),
+            to: record(0, 3),
+            data: Vec::new(),
+        });
+        assert!(matches!(result, Err(Error::InvalidRange)));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{CommitLog, Error, Options};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Bus
+///
+/// A durable, in-process event bus: every topic is a commit log of its own, in a directory
+/// named after it.
+///
+/// Subscribers first receive every record already in the topic (replay), then each record as
+/// it's published. Records are delivered as owned buffers through channels, so receivers can
+/// be moved to other threads.
+pub struct Bus {
+    /// Root directory, holding a directory per topic
+    path: PathBuf,
+
+    /// Settings of the commit log of every topic
+    options: Options,
+
+    /// Topics opened so far, by name
+    topics: HashMap<String, Topic>,
+}
+
+/// Topic
+///
+/// The commit log of a topic and its live subscribers.
+struct Topic {
+    commit_log: CommitLog,
+    subscribers: Vec<Sender<Vec<u8>>>,
+}
+
+impl Bus {
+    /// Return a bus storing its topics in the given directory, topics are opened on first use
+    pub fn new<P: Into<PathBuf>>(path: P, options: Options) -> Self {
+        Self {
+            path: path.into(),
+            options,
+            topics: HashMap::new(),
+        }
+    }
+
+    /// Append the buffer to the topic, delivering it to every subscriber
+    ///
+    /// Subscribers whose receiver was dropped are forgotten.
+    pub fn publish(&mut self, topic: &str, buffer: &[u8]) -> Result<(), Error> {
+        let topic = self.topic(topic)?;
+        topic.commit_log.write(buffer)?;
+        topic
+            .subscribers
+            .retain(|subscriber| subscriber.send(buffer.to_vec()).is_ok());
+
+        Ok(())
+    }
+
+    /// Subscribe to the topic, receiving every record already published then the new ones
+    pub fn subscribe(&mut self, topic: &str) -> Result<Receiver<Vec<u8>>, Error> {
+        let topic = self.topic(topic)?;
+        let (sender, receiver) = mpsc::channel();
+
+        for record in topic.commit_log.iter() {
+            // the receiver is still in scope, sending can't fail
+            let _ = sender.send(record?.to_vec());
+        }
+        topic.subscribers.push(sender);
+
+        Ok(receiver)
+    }
+
+    /// Return the topic, opening its commit log if needed
+    fn topic(&mut self, name: &str) -> Result<&mut Topic, Error> {
+        if !is_valid_topic(name) {
+            return Err(Error::InvalidTopic);
+        }
+
+        if !self.topics.contains_key(name) {
+            let commit_log = CommitLog::with_options(self.path.join(name), self.options.clone())?;
+            let topic = Topic {
+                commit_log,
+                subscribers: Vec::new(),
+            };
+            self.topics.insert(name.to_string(), topic);
+        }
+
+        Ok(self.topics.get_mut(name).unwrap())
+    }
+}
+
+/// Return true if the name can be used as a directory name, without escaping the root
+fn is_valid_topic(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_publish_and_subscribe() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut bus = Bus::new(tmp_dir.clone(), Options::new(100, 1000));
+
+        bus.publish("orders", b"first-order").unwrap();
+        let orders = bus.subscribe("orders").unwrap();
+        let payments = bus.subscribe("payments").unwrap();
+        bus.publish("orders", b"second-order").unwrap();
+
+        // replayed, then delivered live
+        assert_eq!(orders.recv().unwrap(), b"first-order");
+        assert_eq!(orders.recv().unwrap(), b"second-order");
+        assert!(orders.try_recv().is_err());
+        assert!(payments.try_recv().is_err());
+
+        // dropped subscribers don't fail publishing
+        drop(orders);
+        bus.publish("orders", b"third-order").unwrap();
+
+        // topics are durable
+        drop(bus);
+        let mut bus = Bus::new(tmp_dir, Options::new(100, 1000));
+        let orders = bus.subscribe("orders").unwrap();
+        assert_eq!(orders.iter().take(3).count(), 3);
+    }
+
+    #[test]
+    fn test_invalid_topic() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut bus = Bus::new(tmp_dir, Options::new(100, 1000));
+
+        for topic in &["", "..", "../escape", "a/b", ".hidden"] {
+            assert!(matches!(
+                bus.publish(topic, b"record"),
+                Err(Error::InvalidTopic)
+            ));
+        }
+    }
+}
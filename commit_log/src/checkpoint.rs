@@ -0,0 +1,283 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::format::{
+    CHECKPOINT_GENERATION_AT as GENERATION_AT, CHECKPOINT_HEADER_SIZE as HEADER_SIZE,
+    CHECKPOINT_LENGTH_AT as LENGTH_AT, CHECKPOINT_MAGIC as MAGIC, CRC_SIZE, MAGIC_SIZE,
+};
+use crc::crc32;
+use derive_more::From;
+
+#[derive(Debug, From)]
+pub enum Error {
+    Io(io::Error),
+    /// The slot does not start with the checkpoint magic bytes
+    InvalidMagic,
+    /// The slot is shorter than its header claims
+    Truncated,
+    /// The stored CRC does not match the slot content
+    ChecksumMismatch,
+    /// Both slots exist, but none of them is valid
+    Corrupted,
+    /// The payload isn't a value of a supported version, see `Codec`
+    Codec(crate::codec::Error),
+}
+
+/// Checkpoint
+///
+/// A small, crash-safe file to persist state that is rewritten over and over (e.g. offsets).
+///
+/// Writes are double-buffered across two slots, each write goes to the slot that does not hold
+/// the latest generation, so a crash mid-write can only damage the older copy:
+///
+/// offsets.ckp.0
+/// offsets.ckp.1
+///
+/// Every write goes through a temporary file that is fsynced and then renamed over the slot.
+///
+/// Each slot is laid out as:
+///
+/// |-------|------------|--------|---------|-------|
+/// | magic | generation | length | payload | crc32 |
+/// |-------|------------|--------|---------|-------|
+///   4B        8B          4B      length     4B
+///
+/// The CRC32C covers everything before it. On load, both slots are validated and the valid one
+/// with the highest generation wins.
+///
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// Base path of the checkpoint, slots are suffixed with `.0`/`.1`
+    path: PathBuf,
+
+    /// Generation of the latest valid write (0 when nothing was written)
+    generation: u64,
+}
+
+impl Checkpoint {
+    /// Open a checkpoint, reading the generation of the existing slots (if any)
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let mut checkpoint = Self {
+            path: path.into(),
+            generation: 0,
+        };
+
+        if let Some((generation, _)) = checkpoint.latest()? {
+            checkpoint.generation = generation;
+        }
+
+        Ok(checkpoint)
+    }
+
+    /// Generation of the latest write
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Load the payload of the latest valid slot, `None` if nothing was ever written
+    pub fn load(&self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.latest()?.map(|(_, payload)| payload))
+    }
+
+    /// Durably store a new payload, returning its generation
+    pub fn store(&mut self, payload: &[u8]) -> Result<u64, Error> {
+        let generation = self.generation + 1;
+
+        let mut buffer = Vec::with_capacity(HEADER_SIZE + payload.len() + CRC_SIZE);
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&generation.to_le_bytes());
+        buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(payload);
+        let crc = crc32::checksum_castagnoli(&buffer);
+        buffer.extend_from_slice(&crc.to_le_bytes());
+
+        let tmp = self.slot_path("tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+
+        fs::rename(&tmp, self.slot_path(&(generation % 2).to_string()))?;
+        self.sync_parent()?;
+
+        self.generation = generation;
+        Ok(generation)
+    }
+
+    /// Read both slots, returning the valid one with the highest generation
+    ///
+    /// Why a slot is invalid (e.g. `Truncated` for a torn write, `InvalidMagic` for a foreign
+    /// file) is logged, when no slot is valid `Corrupted` is returned.
+    fn latest(&self) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let mut found = false;
+        let mut latest: Option<(u64, Vec<u8>)> = None;
+
+        for slot in 0..2 {
+            let slot_path = self.slot_path(&slot.to_string());
+            let buffer = match read_file(&slot_path) {
+                Ok(buffer) => buffer,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            found = true;
+
+            match decode(&buffer) {
+                Ok((generation, payload)) => {
+                    if latest.as_ref().is_none_or(|(g, _)| generation > *g) {
+                        latest = Some((generation, payload.to_vec()));
+                    }
+                }
+                Err(e) => log::warn!("invalid checkpoint slot {:?}: {:?}", slot_path, e),
+            }
+        }
+
+        if found && latest.is_none() {
+            return Err(Error::Corrupted);
+        }
+
+        Ok(latest)
+    }
+
+    fn slot_path(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(suffix);
+        self.path.with_file_name(name)
+    }
+
+    /// Fsync the directory to make the rename itself durable
+    fn sync_parent(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Validate a slot, returning its generation and payload
+fn decode(buffer: &[u8]) -> Result<(u64, &[u8]), Error> {
+    if buffer.len() < HEADER_SIZE + CRC_SIZE {
+        return Err(Error::Truncated);
+    }
+
+    if &buffer[0..MAGIC_SIZE] != MAGIC {
+        return Err(Error::InvalidMagic);
+    }
+
+    let mut generation = [0; 8];
+    generation.copy_from_slice(&buffer[GENERATION_AT..LENGTH_AT]);
+    let mut length = [0; 4];
+    length.copy_from_slice(&buffer[LENGTH_AT..HEADER_SIZE]);
+    let length = u32::from_le_bytes(length) as usize;
+
+    if buffer.len() != HEADER_SIZE + length + CRC_SIZE {
+        return Err(Error::Truncated);
+    }
+
+    let mut crc = [0; CRC_SIZE];
+    crc.copy_from_slice(&buffer[(HEADER_SIZE + length)..]);
+    if crc32::checksum_castagnoli(&buffer[0..(HEADER_SIZE + length)]) != u32::from_le_bytes(crc) {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok((
+        u64::from_le_bytes(generation),
+        &buffer[HEADER_SIZE..(HEADER_SIZE + length)],
+    ))
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    File::open(path)?.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_empty() {
+        let tmp_dir = tempdir().unwrap();
+        let c = Checkpoint::open(tmp_dir.path().join("offsets.ckp")).unwrap();
+
+        assert_eq!(c.generation(), 0);
+        assert_eq!(c.load().unwrap(), None);
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("offsets.ckp");
+        let mut c = Checkpoint::open(path.clone()).unwrap();
+
+        assert_eq!(c.store(b"first").unwrap(), 1);
+        assert_eq!(c.store(b"second").unwrap(), 2);
+        assert_eq!(c.load().unwrap(), Some(b"second".to_vec()));
+
+        // slots alternate between writes
+        assert!(tmp_dir.path().join("offsets.ckp.0").exists());
+        assert!(tmp_dir.path().join("offsets.ckp.1").exists());
+        assert!(!tmp_dir.path().join("offsets.ckp.tmp").exists());
+
+        // reopening picks up the latest generation
+        let c = Checkpoint::open(path).unwrap();
+        assert_eq!(c.generation(), 2);
+        assert_eq!(c.load().unwrap(), Some(b"second".to_vec()));
+    }
+
+    #[test]
+    fn test_fallback_to_previous_generation() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("offsets.ckp");
+        let mut c = Checkpoint::open(path.clone()).unwrap();
+        c.store(b"first").unwrap();
+        c.store(b"second").unwrap();
+
+        // flip a payload byte of the latest slot
+        let latest = tmp_dir.path().join("offsets.ckp.0");
+        let mut buffer = fs::read(&latest).unwrap();
+        buffer[HEADER_SIZE] ^= 0xff;
+        fs::write(&latest, buffer).unwrap();
+
+        let c = Checkpoint::open(path).unwrap();
+        assert_eq!(c.generation(), 1);
+        assert_eq!(c.load().unwrap(), Some(b"first".to_vec()));
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("offsets.ckp");
+        fs::write(tmp_dir.path().join("offsets.ckp.1"), b"garbage").unwrap();
+
+        match Checkpoint::open(path) {
+            Err(Error::Corrupted) => {}
+            other => panic!("expected Error::Corrupted, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode(b"VCKP"), Err(Error::Truncated)));
+        assert!(matches!(
+            decode(b"XXXX00000000\x00\x00\x00\x000000"),
+            Err(Error::InvalidMagic)
+        ));
+        assert!(matches!(
+            decode(b"VCKP00000000\x00\x00\x00\x000000"),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+}
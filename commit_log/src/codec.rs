@@ -0,0 +1,295 @@
+use crate::format::{RECORD_SIZE, WIRE_VERSION};
+use crate::segment::Entry;
+use crate::{Position, Record};
+
+use std::convert::TryInto;
+
+use derive_more::From;
+
+#[derive(Debug, From)]
+pub enum Error {
+    /// The value was encoded by a version of the format this one can't read
+    #[from(ignore)]
+    UnsupportedVersion(u32),
+    /// The bytes don't hold a value of the expected type
+    Malformed,
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+/// Encoding
+///
+/// How values are serialized by `Codec`, picked at runtime.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    /// Compact little-endian binary:
+    ///
+    /// |---------|-------------------------|
+    /// | version | fields, u64 each (LE)   |
+    /// |---------|-------------------------|
+    ///     1B              8B * n
+    #[default]
+    Binary,
+    /// A JSON object such as `{"version":1,"value":{"current_offset":3,"segment_index":0}}`
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Serde
+///
+/// Bound on the serde traits when the `json` feature is enabled, none otherwise.
+#[cfg(feature = "json")]
+pub trait Serde: serde::Serialize + serde::de::DeserializeOwned {}
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Serde for T {}
+
+/// Serde
+///
+/// Bound on the serde traits when the `json` feature is enabled, none otherwise.
+#[cfg(not(feature = "json"))]
+pub trait Serde {}
+#[cfg(not(feature = "json"))]
+impl<T> Serde for T {}
+
+/// Codec
+///
+/// Versioned serialization of the values exchanged with other processes or persisted outside
+/// of the segments, such as cursors and fences. Every encoding carries the version of the
+/// format (`WIRE_VERSION`), values of other versions are refused rather than misread.
+pub trait Codec: Serde + Sized {
+    /// Return the fields of the value, in the order of its binary layout
+    fn fields(&self) -> Vec<u64>;
+
+    /// Return the value of the given fields, `None` if they don't make one
+    fn from_fields(fields: &[u64]) -> Option<Self>;
+
+    /// Encode the value
+    fn encode(&self, encoding: Encoding) -> Vec<u8> {
+        match encoding {
+            Encoding::Binary => {
+                let fields = self.fields();
+                let mut bytes = Vec::with_capacity(1 + 8 * fields.len());
+                bytes.push(WIRE_VERSION as u8);
+                for field in fields {
+                    bytes.extend_from_slice(&field.to_le_bytes());
+                }
+                bytes
+            }
+            #[cfg(feature = "json")]
+            Encoding::Json => serde_json::to_vec(&serde_json::json!({
+                "version": WIRE_VERSION,
+                "value": self,
+            }))
+            .expect("values are always serializable"),
+        }
+    }
+
+    /// Decode a value encoded by `encode`
+    fn decode(bytes: &[u8], encoding: Encoding) -> Result<Self, Error> {
+        match encoding {
+            Encoding::Binary => {
+                let version = u32::from(*bytes.first().ok_or(Error::Malformed)?);
+                if version != WIRE_VERSION {
+                    return Err(Error::UnsupportedVersion(version));
+                }
+
+                let fields = fields(&bytes[1..]).ok_or(Error::Malformed)?;
+                Self::from_fields(&fields).ok_or(Error::Malformed)
+            }
+            #[cfg(feature = "json")]
+            Encoding::Json => {
+                #[derive(serde::Deserialize)]
+                struct Versioned {
+                    version: u32,
+                    value: serde_json::Value,
+                }
+
+                let versioned: Versioned = serde_json::from_slice(bytes)?;
+                if versioned.version != WIRE_VERSION {
+                    return Err(Error::UnsupportedVersion(versioned.version));
+                }
+                Ok(serde_json::from_value(versioned.value)?)
+            }
+        }
+    }
+}
+
+/// Split the bytes into little-endian u64 fields, `None` if bytes are left over
+fn fields(bytes: &[u8]) -> Option<Vec<u64>> {
+    let chunks = bytes.chunks_exact(8);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+
+    Some(
+        chunks
+            .map(|field| u64::from_le_bytes(field.try_into().unwrap()))
+            .collect(),
+    )
+}
+
+impl Codec for Record {
+    fn fields(&self) -> Vec<u64> {
+        vec![self.segment_index as u64, self.current_offset as u64]
+    }
+
+    fn from_fields(fields: &[u64]) -> Option<Self> {
+        match *fields {
+            [segment_index, current_offset] => Some(Self {
+                segment_index: segment_index as usize,
+                current_offset: current_offset as usize,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Record {
+    /// Decode a record persisted by a `Checkpoint` (cursors, fences) with the binary encoding
+    ///
+    /// Records persisted before the format was versioned are bare fields, those are accepted
+    /// too. Checkpoints checksum their payloads, so they can't be mistaken for a truncated
+    /// versioned record.
+    pub(crate) fn decode_persisted(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != RECORD_SIZE {
+            return Self::decode(bytes, Encoding::Binary);
+        }
+
+        fields(bytes)
+            .and_then(|fields| Self::from_fields(&fields))
+            .ok_or(Error::Malformed)
+    }
+}
+
+impl Codec for Entry {
+    fn fields(&self) -> Vec<u64> {
+        vec![self.offset as u64, self.size as u64]
+    }
+
+    fn from_fields(fields: &[u64]) -> Option<Self> {
+        match *fields {
+            [offset, size] => Some(Self::new(offset as usize, size as usize)),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for Position {
+    fn fields(&self) -> Vec<u64> {
+        match self {
+            Position::Horizon => vec![0],
+            Position::Offset(offset) => vec![1, *offset as u64],
+        }
+    }
+
+    fn from_fields(fields: &[u64]) -> Option<Self> {
+        match *fields {
+            [0] => Some(Position::Horizon),
+            [1, offset] => Some(Position::Offset(offset as usize)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        Record {
+            segment_index: 2,
+            current_offset: 7,
+        }
+    }
+
+    #[test]
+    fn test_binary() {
+        let bytes = record().encode(Encoding::Binary);
+        assert_eq!(bytes.len(), 1 + RECORD_SIZE);
+        assert_eq!(bytes[0], WIRE_VERSION as u8);
+        assert_eq!(Record::decode(&bytes, Encoding::Binary).unwrap(), record());
+
+        let entry = Entry::new(32, 25);
+        let bytes = entry.encode(Encoding::Binary);
+        assert_eq!(Entry::decode(&bytes, Encoding::Binary).unwrap(), entry);
+
+        for position in [Position::Horizon, Position::Offset(9)] {
+            let bytes = position.encode(Encoding::Binary);
+            assert_eq!(
+                Position::decode(&bytes, Encoding::Binary).unwrap(),
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        // version 0, the bare fields, only ever persisted by checkpoints
+        let mut legacy = Vec::new();
+        legacy.extend_from_slice(&2_u64.to_le_bytes());
+        legacy.extend_from_slice(&7_u64.to_le_bytes());
+        assert_eq!(Record::decode_persisted(&legacy).unwrap(), record());
+        assert!(Record::decode(&legacy, Encoding::Binary).is_err());
+        let bytes = record().encode(Encoding::Binary);
+        assert_eq!(Record::decode_persisted(&bytes).unwrap(), record());
+
+        // a version from the future
+        let mut bytes = record().encode(Encoding::Binary);
+        bytes[0] = WIRE_VERSION as u8 + 1;
+        assert!(matches!(
+            Record::decode(&bytes, Encoding::Binary),
+            Err(Error::UnsupportedVersion(version)) if version == WIRE_VERSION + 1
+        ));
+
+        assert!(matches!(
+            Position::decode(&[WIRE_VERSION as u8], Encoding::Binary),
+            Err(Error::Malformed)
+        ));
+
+        // truncated or padded values
+        let bytes = record().encode(Encoding::Binary);
+        for len in 1..bytes.len() {
+            assert!(Record::decode(&bytes[..len], Encoding::Binary).is_err());
+        }
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(matches!(
+            Record::decode(&padded, Encoding::Binary),
+            Err(Error::Malformed)
+        ));
+        let mut padded = legacy.clone();
+        padded.push(0);
+        assert!(Record::decode_persisted(&padded).is_err());
+        assert!(matches!(
+            Record::decode(&[], Encoding::Binary),
+            Err(Error::Malformed)
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        let bytes = record().encode(Encoding::Json);
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            r#"{"value":{"current_offset":7,"segment_index":2},"version":1}"#
+        );
+        assert_eq!(Record::decode(&bytes, Encoding::Json).unwrap(), record());
+
+        let entry = Entry::new(32, 25);
+        let bytes = entry.encode(Encoding::Json);
+        assert_eq!(Entry::decode(&bytes, Encoding::Json).unwrap(), entry);
+
+        let bytes = Position::Offset(9).encode(Encoding::Json);
+        assert_eq!(
+            Position::decode(&bytes, Encoding::Json).unwrap(),
+            Position::Offset(9)
+        );
+
+        let future = br#"{"version":2,"value":{"current_offset":7,"segment_index":2}}"#;
+        assert!(matches!(
+            Record::decode(future, Encoding::Json),
+            Err(Error::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{CommitLog, Error, Reader, Record};
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Stdout, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// SourceConnector
+///
+/// Pulls records from an external system, to be appended to a commit log.
+pub trait SourceConnector {
+    /// Return the next record, or `None` when nothing is available at the moment
+    fn poll(&mut self) -> Result<Option<Vec<u8>>, Error>;
+}
+
+/// SinkConnector
+///
+/// Pushes records read from a commit log to an external system.
+pub trait SinkConnector {
+    /// Deliver a record
+    fn put(&mut self, record: &[u8]) -> Result<(), Error>;
+
+    /// Ensure the delivered records reached the external system
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Append every record currently available on the source to the commit log.
+///
+/// Returns the amount of records moved.
+pub fn run_source<S: SourceConnector>(
+    source: &mut S,
+    commit_log: &mut CommitLog,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    while let Some(record) = source.poll()? {
+        commit_log.write(&record)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Deliver records to the sink, starting at `record`, within the given budgets.
+///
+/// See `Reader::read_budgeted`. Returns the record to resume from on the next run.
+pub fn run_sink<S: SinkConnector>(
+    sink: &mut S,
+    commit_log: &CommitLog,
+    record: &Record,
+    max_bytes: usize,
+    deadline: Instant,
+) -> Result<Record, Error> {
+    let reader = Reader::new(commit_log);
+    let progress = reader.read_budgeted(record, max_bytes, deadline)?;
+
+    for buf in progress.records {
+        sink.put(buf)?;
+    }
+    sink.flush()?;
+
+    Ok(progress.next)
+}
+
+/// FileSource
+///
+/// Tails a file, producing one record per line (without the line break).
+///
+/// Reaching the end of the file is not final, lines appended later are picked up by the
+/// following polls. An incomplete last line is held until its line break is written.
+pub struct FileSource {
+    reader: BufReader<File>,
+    pending: Vec<u8>,
+}
+
+impl FileSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            pending: Vec::new(),
+        })
+    }
+}
+
+impl SourceConnector for FileSource {
+    fn poll(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        self.reader.read_until(b'\n', &mut self.pending)?;
+
+        if self.pending.last() != Some(&b'\n') {
+            return Ok(None);
+        }
+
+        self.pending.pop();
+        Ok(Some(self.pending.split_off(0)))
+    }
+}
+
+/// LineSink
+///
+/// Writes one record per line to any writer.
+pub struct LineSink<W: Write> {
+    writer: W,
+}
+
+/// Appends records to a file, one per line
+pub type FileSink = LineSink<BufWriter<File>>;
+
+/// Prints records to the standard output, one per line
+pub type StdoutSink = LineSink<Stdout>;
+
+impl<W: Write> LineSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl FileSink {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl StdoutSink {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> SinkConnector for LineSink<W> {
+    fn put(&mut self, record: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_file_source() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("input.txt");
+        fs::write(&path, b"first\nsecond\nincomp").unwrap();
+
+        let mut source = FileSource::open(&path).unwrap();
+        assert_eq!(source.poll().unwrap(), Some(b"first".to_vec()));
+        assert_eq!(source.poll().unwrap(), Some(b"second".to_vec()));
+        assert_eq!(source.poll().unwrap(), None);
+
+        // the incomplete line is picked up once it's finished
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"lete\n").unwrap();
+        assert_eq!(source.poll().unwrap(), Some(b"incomplete".to_vec()));
+        assert_eq!(source.poll().unwrap(), None);
+    }
+
+    #[test]
+    fn test_source_to_sink() {
+        let tmp_dir = tempdir().unwrap();
+        let input = tmp_dir.path().join("input.txt");
+        let output = tmp_dir.path().join("output.txt");
+        fs::write(
+            &input,
+            b"this-has-less-20b\nsecond-record\nthird-record-bigger-goes-to-another-segment\n",
+        )
+        .unwrap();
+
+        let mut c = CommitLog::new(tmp_dir.path().join("log"), 92, 10000).unwrap();
+        let mut source = FileSource::open(&input).unwrap();
+        assert_eq!(run_source(&mut source, &mut c).unwrap(), 3);
+
+        let mut sink = FileSink::create(&output).unwrap();
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let next = run_sink(&mut sink, &c, &record, 1000, deadline).unwrap();
+
+        assert_eq!(next.segment_index, 1);
+        assert_eq!(next.current_offset, 1);
+        assert_eq!(fs::read(&output).unwrap(), fs::read(&input).unwrap());
+    }
+}
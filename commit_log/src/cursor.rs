@@ -0,0 +1,70 @@
+use crate::checkpoint::{Checkpoint, Error};
+use crate::codec::{Codec, Encoding};
+use crate::Record;
+
+use std::path::Path;
+
+/// Cursor
+///
+/// The position to resume reading a commit log from, that can be durably saved and loaded
+/// back across restarts.
+///
+/// Saving goes through a `Checkpoint`, so it's atomic and checksummed: a crash mid-save leaves
+/// the previously saved position in place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    /// The next record to be read
+    pub record: Record,
+}
+
+impl Cursor {
+    pub fn new(record: Record) -> Self {
+        Self { record }
+    }
+
+    /// Durably save the cursor to the given path
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        Checkpoint::open(path.as_ref())?.store(&self.record.encode(Encoding::Binary))?;
+        Ok(())
+    }
+
+    /// Load the cursor saved at the given path, `None` if it was never saved
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>, Error> {
+        let payload = match Checkpoint::open(path.as_ref())?.load()? {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+
+        let record = Record::decode_persisted(&payload)?;
+        Ok(Some(Self::new(record)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_load() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("consumer.cursor");
+
+        assert_eq!(Cursor::load(&path).unwrap(), None);
+
+        let cursor = Cursor::new(Record {
+            segment_index: 3,
+            current_offset: 42,
+        });
+        cursor.save(&path).unwrap();
+        assert_eq!(Cursor::load(&path).unwrap(), Some(cursor));
+
+        let cursor = Cursor::new(Record {
+            segment_index: 4,
+            current_offset: 0,
+        });
+        cursor.save(&path).unwrap();
+        assert_eq!(Cursor::load(&path).unwrap(), Some(cursor));
+    }
+}
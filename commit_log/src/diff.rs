@@ -0,0 +1,122 @@
+use crate::{CommitLog, Error};
+
+/// Diff
+///
+/// The outcome of comparing two commit logs record by record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    /// Position (counted across segments) of the first record that differs, or of the first
+    /// record missing from the shorter log. `None` if the logs are identical.
+    pub divergence: Option<usize>,
+    /// Amount of records of the left log
+    pub left_records: usize,
+    /// Amount of records of the right log
+    pub right_records: usize,
+}
+
+impl Diff {
+    pub fn is_identical(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Compare two commit logs record by record, e.g. to validate a mirror or a migration.
+///
+/// Records are compared by content and position only, so logs with different segment sizes
+/// are still identical if they hold the same records in the same order.
+pub fn diff(left: &CommitLog, right: &CommitLog) -> Result<Diff, Error> {
+    let mut left = records(left);
+    let mut right = records(right);
+
+    let mut diff = Diff {
+        divergence: None,
+        left_records: 0,
+        right_records: 0,
+    };
+
+    loop {
+        let (l, r) = (left.next().transpose()?, right.next().transpose()?);
+        if l.is_some() {
+            diff.left_records += 1;
+        }
+        if r.is_some() {
+            diff.right_records += 1;
+        }
+
+        match (l, r) {
+            (None, None) => break,
+            (l, r) if l != r && diff.divergence.is_none() => {
+                diff.divergence = Some(diff.left_records.max(diff.right_records) - 1);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Iterate over every record of the log, in order
+fn records(log: &CommitLog) -> impl Iterator<Item = Result<&[u8], Error>> {
+    log.segments
+        .iter()
+        .flat_map(|segment| match segment.read_many(0, segment.entries()) {
+            Ok(bufs) => bufs.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e.into())],
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    fn log(segment_size: usize, records: &[&str]) -> CommitLog {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, segment_size, 10000).unwrap();
+        for record in records {
+            c.write(record.as_bytes()).unwrap();
+        }
+        c
+    }
+
+    #[test]
+    fn test_identical() {
+        // different segment sizes, same records
+        let records = [
+            "this-has-less-20b",
+            "second-record",
+            "third-record-bigger-goes-to-another-segment",
+        ];
+        let a = log(92, &records);
+        let b = log(1000, &records);
+        assert_eq!(a.segments.len(), 2);
+
+        let d = diff(&a, &b).unwrap();
+        assert!(d.is_identical());
+        assert_eq!(d.left_records, 3);
+        assert_eq!(d.right_records, 3);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let a = log(1000, &["first", "second", "third"]);
+        let b = log(1000, &["first", "changed", "third", "fourth"]);
+
+        let d = diff(&a, &b).unwrap();
+        assert_eq!(d.divergence, Some(1));
+        assert_eq!(d.left_records, 3);
+        assert_eq!(d.right_records, 4);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let a = log(1000, &["first", "second"]);
+        let b = log(1000, &["first"]);
+
+        let d = diff(&a, &b).unwrap();
+        assert_eq!(d.divergence, Some(1));
+        assert_eq!(d.left_records, 2);
+        assert_eq!(d.right_records, 1);
+    }
+}
@@ -0,0 +1,61 @@
+use std::io;
+use std::path::Path;
+
+/// Return the amount of bytes available to unprivileged users on the filesystem of `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Free space is not checked on other platforms
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+/// Return the size of the OS memory pages
+#[cfg(unix)]
+pub fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+/// Pages are assumed to be 4KiB on other platforms
+#[cfg(not(unix))]
+pub fn page_size() -> usize {
+    4096
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_available_space() {
+        let tmp_dir = tempdir().unwrap();
+        assert!(available_space(tmp_dir.path()).unwrap() > 0);
+        assert!(available_space(&tmp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_page_size() {
+        assert!(page_size().is_power_of_two());
+    }
+}
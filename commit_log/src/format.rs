@@ -0,0 +1,127 @@
+//! Format
+//!
+//! Sizes and magic values of everything written to disk, kept in one place so the writers and
+//! readers of each file can't drift apart. The assertions below tie the sizes to their fields,
+//! any refactor that breaks a layout fails to compile.
+
+/// Amount of bytes of a CRC32C
+pub const CRC_SIZE: usize = 4;
+
+/// Amount of bytes of the magic values identifying a file
+pub const MAGIC_SIZE: usize = 4;
+
+/// Amount of bytes for each entry on the index (offset + size, little-endian u64)
+pub const ENTRY_SIZE: usize = 16;
+
+/// Amount of bytes of the header of log and index files
+/// (magic + version + created at + base offset + reserved)
+pub const SEGMENT_HEADER_SIZE: usize = 32;
+
+/// Magic bytes at the beginning of every index
+pub const INDEX_MAGIC: &[u8; MAGIC_SIZE] = b"VIDX";
+
+/// Version of the index format, bumped on every incompatible change
+pub const INDEX_VERSION: u32 = 2;
+
+/// Amount of bytes of the index header, two entry slots
+pub const INDEX_HEADER_SIZE: usize = SEGMENT_HEADER_SIZE;
+
+/// Magic bytes at the beginning of every log
+pub const LOG_MAGIC: &[u8; MAGIC_SIZE] = b"VLOG";
+
+/// Version of the log format, bumped on every incompatible change
+pub const LOG_VERSION: u32 = 1;
+
+/// Amount of bytes of the log header, records start right after it
+pub const LOG_HEADER_SIZE: usize = SEGMENT_HEADER_SIZE;
+
+/// Amount of bytes of the length prefix of a record in the log
+pub const LENGTH_SIZE: usize = 4;
+
+/// Amount of bytes before the payload of a record in the log (crc32 + length)
+pub const RECORD_HEADER_SIZE: usize = CRC_SIZE + LENGTH_SIZE;
+
+/// Max amount of bytes of the payload of a record, bound by its length prefix
+pub const MAX_PAYLOAD_SIZE: usize = u32::MAX as usize;
+
+/// Amount of bytes of an encoded Record (segment index + offset)
+pub const RECORD_SIZE: usize = 16;
+
+/// Magic bytes at the beginning of every checkpoint slot
+pub const CHECKPOINT_MAGIC: &[u8; MAGIC_SIZE] = b"VCKP";
+
+/// Amount of bytes before the payload of a checkpoint slot (magic + generation + length)
+pub const CHECKPOINT_HEADER_SIZE: usize = 16;
+
+/// Position of the generation in a checkpoint slot, right after the magic
+pub const CHECKPOINT_GENERATION_AT: usize = MAGIC_SIZE;
+
+/// Position of the length of the payload in a checkpoint slot
+pub const CHECKPOINT_LENGTH_AT: usize = CHECKPOINT_GENERATION_AT + U64;
+
+/// Amount of bytes before the data of an annotation (4 positions + length)
+pub const ANNOTATION_HEADER_SIZE: usize = 36;
+
+/// Magic bytes at the beginning of every segment footer
+pub const FOOTER_MAGIC: &[u8; MAGIC_SIZE] = b"VFTR";
+
+/// Amount of bytes of a segment footer (magic + entries + size + index crc + crc)
+pub const FOOTER_SIZE: usize = 28;
+
+/// Position of the amount of entries in a segment footer, right after the magic
+pub const FOOTER_ENTRIES_AT: usize = MAGIC_SIZE;
+
+/// Position of the size of the log in a segment footer
+pub const FOOTER_LOG_SIZE_AT: usize = FOOTER_ENTRIES_AT + U64;
+
+/// Position of the CRC32C of the index in a segment footer
+pub const FOOTER_INDEX_CRC_AT: usize = FOOTER_LOG_SIZE_AT + U64;
+
+/// Position of the CRC32C of the footer itself, covering everything before it
+pub const FOOTER_CRC_AT: usize = FOOTER_INDEX_CRC_AT + CRC_SIZE;
+
+/// Version of the manifest format, bumped on every incompatible change
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Amount of bytes of an encoded Manifest before its list of segments
+/// (versions + index layout + segment size + index size + amount of segments)
+pub const MANIFEST_HEADER_SIZE: usize = 40;
+
+/// Amount of bytes before the directory of each segment listed by a Manifest
+/// (base offset + length of the directory)
+pub const MANIFEST_SEGMENT_SIZE: usize = 12;
+
+/// Version of the format of values encoded by `Codec`, bumped on every incompatible change
+pub const WIRE_VERSION: u32 = 1;
+
+const U32: usize = std::mem::size_of::<u32>();
+const U64: usize = std::mem::size_of::<u64>();
+
+const _: () = assert!(CRC_SIZE == U32);
+const _: () = assert!(ENTRY_SIZE == 2 * U64);
+const _: () = assert!(SEGMENT_HEADER_SIZE >= MAGIC_SIZE + U32 + 2 * U64);
+// the header takes whole slots, so entries stay aligned
+const _: () = assert!(INDEX_HEADER_SIZE.is_multiple_of(ENTRY_SIZE));
+const _: () = assert!(RECORD_SIZE == 2 * U64);
+// the version takes a single byte on binary encodings
+const _: () = assert!(WIRE_VERSION <= u8::MAX as u32);
+const _: () = assert!(LENGTH_SIZE == U32);
+const _: () = assert!(RECORD_HEADER_SIZE == CRC_SIZE + LENGTH_SIZE);
+const _: () = assert!(CHECKPOINT_HEADER_SIZE == MAGIC_SIZE + U64 + U32);
+const _: () = assert!(CHECKPOINT_LENGTH_AT + U32 == CHECKPOINT_HEADER_SIZE);
+const _: () = assert!(ANNOTATION_HEADER_SIZE == 2 * RECORD_SIZE + U32);
+const _: () = assert!(MANIFEST_HEADER_SIZE == 4 * U32 + 3 * U64);
+const _: () = assert!(MANIFEST_SEGMENT_SIZE == U64 + U32);
+const _: () = assert!(FOOTER_SIZE == MAGIC_SIZE + 2 * U64 + 2 * CRC_SIZE);
+const _: () = assert!(FOOTER_CRC_AT + CRC_SIZE == FOOTER_SIZE);
+// magic values must tell files apart
+const _: () = assert!(magic(CHECKPOINT_MAGIC) != magic(FOOTER_MAGIC));
+const _: () = assert!(magic(CHECKPOINT_MAGIC) != magic(INDEX_MAGIC));
+const _: () = assert!(magic(FOOTER_MAGIC) != magic(INDEX_MAGIC));
+const _: () = assert!(magic(LOG_MAGIC) != magic(INDEX_MAGIC));
+const _: () = assert!(magic(LOG_MAGIC) != magic(CHECKPOINT_MAGIC));
+const _: () = assert!(magic(LOG_MAGIC) != magic(FOOTER_MAGIC));
+
+const fn magic(bytes: &[u8; MAGIC_SIZE]) -> u32 {
+    u32::from_le_bytes(*bytes)
+}
@@ -0,0 +1,70 @@
+use crate::{Error, Position, Record, Segment};
+
+/// Iter
+///
+/// Walks the records of a commit log in order, across segments, until the end of the log.
+///
+/// Once a read fails the error is returned and the iteration ends, as the following records
+/// can't be reached reliably.
+pub struct Iter<'a> {
+    segments: &'a [Segment],
+
+    /// The next record to be read
+    record: Record,
+}
+
+impl<'a> Iter<'a> {
+    /// Return an iterator starting at the given position of the log
+    ///
+    /// `Position::Offset` counts records from the beginning of the log, across segments.
+    pub(crate) fn new(segments: &'a [Segment], position: &Position) -> Self {
+        let mut record = Record {
+            segment_index: 0,
+            current_offset: 0,
+        };
+
+        if let Position::Offset(offset) = *position {
+            record.current_offset = offset;
+            while record.segment_index < segments.len()
+                && record.current_offset >= segments[record.segment_index].entries()
+            {
+                record.current_offset -= segments[record.segment_index].entries();
+                record.segment_index += 1;
+            }
+        }
+
+        Self { segments, record }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<&'a [u8], Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let segment = self.segments.get(self.record.segment_index)?;
+
+            if self.record.current_offset < segment.entries() {
+                break;
+            }
+
+            // the last segment is never skipped, its records may still be written
+            if self.record.segment_index + 1 == self.segments.len() {
+                return None;
+            }
+            self.record.segment_index += 1;
+            self.record.current_offset = 0;
+        }
+
+        match self.segments[self.record.segment_index].read_at(self.record.current_offset) {
+            Ok(buf) => {
+                self.record.current_offset += 1;
+                Some(Ok(buf))
+            }
+            Err(e) => {
+                self.record.segment_index = self.segments.len();
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+#[cfg(feature = "latency")]
+use hdrhistogram::Histogram;
+#[cfg(feature = "latency")]
+use std::fmt;
+#[cfg(feature = "latency")]
+use std::time::Instant;
+
+/// Stage
+///
+/// The stages of an append, timed separately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// Checking whether the buffer fits in the active segment
+    Fit,
+    /// Writing the entry to the index
+    IndexWrite,
+    /// Writing the buffer to the log
+    LogWrite,
+    /// Rotating to a new segment (flush + segment creation)
+    Rotation,
+}
+
+/// Latency
+///
+/// Per-stage latency histograms of the append path, in nanoseconds.
+///
+/// Without the `latency` feature this is a zero-sized no-op, so the append path doesn't pay
+/// for reading the clock.
+#[cfg(feature = "latency")]
+#[derive(Debug, Clone)]
+pub struct Latency {
+    pub fit: Histogram<u64>,
+    pub index_write: Histogram<u64>,
+    pub log_write: Histogram<u64>,
+    pub rotation: Histogram<u64>,
+}
+
+#[cfg(not(feature = "latency"))]
+#[derive(Debug, Default, Clone)]
+pub struct Latency {
+    _private: (),
+}
+
+/// Token returned when a stage starts, to be given back when recording it
+#[cfg(feature = "latency")]
+pub type Timer = Instant;
+
+#[cfg(not(feature = "latency"))]
+#[derive(Debug, Clone, Copy)]
+pub struct Timer;
+
+/// Percentiles reported for every stage
+#[cfg(feature = "latency")]
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 99.9];
+
+#[cfg(feature = "latency")]
+impl Default for Latency {
+    fn default() -> Self {
+        // 3 significant figures, auto-resizing to any duration
+        let histogram = || Histogram::new(3).expect("valid histogram precision");
+        Self {
+            fit: histogram(),
+            index_write: histogram(),
+            log_write: histogram(),
+            rotation: histogram(),
+        }
+    }
+}
+
+#[cfg(feature = "latency")]
+impl Latency {
+    /// Start timing a stage
+    #[inline]
+    pub fn start(&self) -> Timer {
+        Instant::now()
+    }
+
+    /// Record the time elapsed since `start` for the given stage
+    #[inline]
+    pub fn record(&mut self, stage: Stage, start: Timer) {
+        let elapsed = start.elapsed().as_nanos() as u64;
+        self.histogram_mut(stage).saturating_record(elapsed);
+    }
+
+    /// Return the histogram of the given stage
+    pub fn histogram(&self, stage: Stage) -> &Histogram<u64> {
+        match stage {
+            Stage::Fit => &self.fit,
+            Stage::IndexWrite => &self.index_write,
+            Stage::LogWrite => &self.log_write,
+            Stage::Rotation => &self.rotation,
+        }
+    }
+
+    fn histogram_mut(&mut self, stage: Stage) -> &mut Histogram<u64> {
+        match stage {
+            Stage::Fit => &mut self.fit,
+            Stage::IndexWrite => &mut self.index_write,
+            Stage::LogWrite => &mut self.log_write,
+            Stage::Rotation => &mut self.rotation,
+        }
+    }
+}
+
+#[cfg(not(feature = "latency"))]
+impl Latency {
+    #[inline]
+    pub fn start(&self) -> Timer {
+        Timer
+    }
+
+    #[inline]
+    pub fn record(&mut self, _stage: Stage, _start: Timer) {}
+}
+
+/// Export the percentiles of every stage as a table, e.g.:
+///
+/// stage            count      p50      p90      p99    p99.9      max
+/// fit               1000       42       84      125      250      917
+#[cfg(feature = "latency")]
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<12} {:>9}", "stage", "count")?;
+        for p in PERCENTILES.iter() {
+            write!(f, " {:>8}", format!("p{}", p))?;
+        }
+        writeln!(f, " {:>8}", "max")?;
+
+        for (name, stage) in [
+            ("fit", Stage::Fit),
+            ("index_write", Stage::IndexWrite),
+            ("log_write", Stage::LogWrite),
+            ("rotation", Stage::Rotation),
+        ]
+        .iter()
+        {
+            let histogram = self.histogram(*stage);
+            write!(f, "{:<12} {:>9}", name, histogram.len())?;
+            for p in PERCENTILES.iter() {
+                write!(f, " {:>8}", histogram.value_at_percentile(*p))?;
+            }
+            writeln!(f, " {:>8}", histogram.max())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "latency"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut l = Latency::default();
+
+        let start = l.start();
+        l.record(Stage::LogWrite, start);
+        l.record(Stage::LogWrite, start);
+
+        assert_eq!(l.histogram(Stage::LogWrite).len(), 2);
+        assert_eq!(l.histogram(Stage::Fit).len(), 0);
+    }
+
+    #[test]
+    fn test_display() {
+        let l = Latency::default();
+        let report = l.to_string();
+
+        assert!(report.starts_with("stage"));
+        assert!(report.contains("p99.9"));
+        assert_eq!(report.lines().count(), 5);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bucketing
+///
+/// How segment files are grouped into directories under the root of the log. Reopening finds
+/// segments in any of them, so the bucketing can be changed between runs.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Bucketing {
+    /// All segments at the root of the log.
+    #[default]
+    Flat,
+    /// In a directory per day (UTC) the segment was created on, e.g. `2024-05-10/`, so
+    /// retention by age can drop whole directories.
+    Daily,
+    /// In the directory named by the given function, from the time the segment is created
+    /// on. Names must not start with `.`, those are reserved for staging.
+    Custom(fn(SystemTime) -> String),
+}
+
+impl Bucketing {
+    /// Return the directory for a segment created at `time`, under `root`
+    pub fn dir(&self, root: &Path, time: SystemTime) -> PathBuf {
+        match self {
+            Bucketing::Flat => root.to_path_buf(),
+            Bucketing::Daily => root.join(date(time)),
+            Bucketing::Custom(name) => root.join(name(time)),
+        }
+    }
+}
+
+/// Return the UTC date of `time` as `YYYY-MM-DD`
+pub fn date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400);
+
+    // civil from days, shifting the year to start in March so leap days come last
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Find the segments under `root`, at the root or in a bucket directory
+///
+/// Returns the offset of every segment along with its directory, sorted by offset. Hidden
+/// directories (e.g. `.bulk`) are skipped.
+pub fn find_segments(root: &Path) -> io::Result<Vec<(usize, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            if let Some(offset) = offset_of(&path) {
+                segments.push((offset, root.to_path_buf()));
+            }
+            continue;
+        }
+
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        for entry in fs::read_dir(&path)? {
+            if let Some(offset) = offset_of(&entry?.path()) {
+                segments.push((offset, path.clone()));
+            }
+        }
+    }
+    segments.sort_unstable();
+
+    Ok(segments)
+}
+
+/// Return the offset of a log file, named after it, e.g. 00000000000000000001.log
+fn offset_of(path: &Path) -> Option<usize> {
+    if path.extension().is_none_or(|extension| extension != "log") {
+        return None;
+    }
+
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(UNIX_EPOCH), "1970-01-01");
+        // 2024-02-29T23:59:59Z
+        let leap = UNIX_EPOCH + Duration::from_secs(1_709_251_199);
+        assert_eq!(date(leap), "2024-02-29");
+        assert_eq!(date(leap + Duration::from_secs(1)), "2024-03-01");
+        // 2000-12-31T12:00:00Z
+        let end = UNIX_EPOCH + Duration::from_secs(978_264_000);
+        assert_eq!(date(end), "2000-12-31");
+    }
+
+    #[test]
+    fn test_find_segments() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let day = root.join("2024-05-10");
+        let staging = root.join(".bulk");
+        fs::create_dir_all(&day).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+
+        File::create(root.join("00000000000000000000.log")).unwrap();
+        File::create(root.join("00000000000000000000.idx")).unwrap();
+        File::create(day.join("00000000000000000001.log")).unwrap();
+        File::create(staging.join("00000000000000000002.log")).unwrap();
+
+        assert_eq!(
+            find_segments(root).unwrap(),
+            vec![(0, root.to_path_buf()), (1, day)]
+        );
+    }
+}
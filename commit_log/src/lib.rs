@@ -1,13 +1,53 @@
 extern crate memmap;
+mod annotation;
+mod bus;
+mod checkpoint;
+mod codec;
+pub mod connector;
+mod cursor;
+mod diff;
+mod disk;
+mod format;
+mod iter;
+mod latency;
+mod layout;
+mod manifest;
 mod reader;
+mod registry;
+mod sampler;
 mod segment;
+mod telemetry;
+mod validation;
+mod watchdog;
 
-use self::segment::Segment;
-pub use reader::Reader;
+use self::annotation::Annotations;
+use self::format::{LOG_HEADER_SIZE, MAX_PAYLOAD_SIZE};
+use self::registry::Registration;
+use self::sampler::Sampler;
+use self::segment::{frame_size, index_size_for};
+pub use self::segment::{Entry, Error as SegmentError, Segment, Violation};
+use self::watchdog::Watchdog;
+pub use annotation::{Annotation, Error as AnnotationError};
+pub use bus::Bus;
+pub use checkpoint::{Checkpoint, Error as CheckpointError};
+pub use codec::{Codec, Encoding, Error as CodecError};
+pub use cursor::Cursor;
+pub use diff::{diff, Diff};
+pub use iter::Iter;
+pub use latency::{Latency, Stage};
+pub use layout::Bucketing;
+pub use manifest::Manifest;
+pub use reader::{Batch, Fetch, Isolation, Progress, Reader};
+pub use sampler::Sampling;
+pub use telemetry::{Operation, SlowOperation};
+pub use validation::{Error as ValidationError, Validation};
 
-use std::fs;
+use std::fs::{self, File};
 use std::io;
-use std::path::PathBuf;
+use std::ops::{Add, Sub};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use derive_more::From;
 
@@ -15,16 +55,221 @@ use derive_more::From;
 pub enum Error {
     Io(io::Error),
     Segment(segment::Error),
+    Reader(reader::Error),
+    Validation(validation::Error),
+    Annotation(annotation::Error),
+    Checkpoint(checkpoint::Error),
     BufferSizeExceeded,
     SegmentUnavailable,
+    HeadMismatch,
+    InsufficientDiskSpace,
+    /// A flush exceeded `Options::flush_deadline`, the log no longer accepts writes
+    Degraded,
+    /// Another CommitLog of this process already uses the directory
+    AlreadyOpen,
+    /// The segment is missing from the directory, while later ones exist, or isn't where the
+    /// manifest lists it
+    #[from(ignore)]
+    MissingSegment(usize),
+    /// The segment is in more than one directory, see `Bucketing`
+    #[from(ignore)]
+    DuplicateSegment(usize),
+    /// The topic name can't be used as a directory name, see `Bus`
+    InvalidTopic,
+    /// The log was created with settings it can't be opened with, see `Manifest`
+    #[from(ignore)]
+    ManifestMismatch(Manifest),
+    /// The manifest of the log isn't of a supported version
+    UnsupportedManifest,
+    /// The log was opened read-only, see `CommitLog::open_read_only`
+    ReadOnly,
 }
 
+/// OversizedSegment
+///
+/// What to do when a segment file found on disk is bigger than the configured size.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OversizedSegment {
+    /// Refuse to open it.
+    #[default]
+    Error,
+    /// Use the size on disk as the max size of the file.
+    Adopt,
+    /// Keep the size on disk, but never write to the segment again.
+    Seal,
+}
+
+/// IndexLayout
+///
+/// How entries are laid out on the index files.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum IndexLayout {
+    /// Entries right after each other.
+    #[default]
+    Packed,
+    /// Entries never straddle OS pages, the end of every page is left as padding. As OS
+    /// pages are a multiple of the 16 bytes of an entry, entries are laid out as when packed,
+    /// only the size of the index is rounded up to whole pages.
+    PageAligned,
+}
+
+/// StuckFlush
+///
+/// What to do when a flush exceeds the configured deadline.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum StuckFlush {
+    /// Mark the log as degraded, refusing further writes with `Error::Degraded`.
+    #[default]
+    Degrade,
+    /// Abort the process.
+    Abort,
+}
+
+/// FlushPolicy
+///
+/// When the active segment is flushed by writes, trading throughput for how much a crash can
+/// lose. Rotations always flush the segment being sealed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FlushPolicy {
+    /// Only when asked to (`flush_until`, `barrier`) or past `Options::flush_dirty_bytes`.
+    #[default]
+    Manual,
+    /// After every write.
+    EveryWrite,
+    /// Once the given amount of records were written since the last flush.
+    EveryRecords(usize),
+    /// On the first write after the given duration passed since the last flush, there is no
+    /// background flush while no writes happen.
+    Interval(Duration),
+}
+
+/// Options
+///
+/// Settings of a commit log, given when creating it.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Size in bytes for the segments
+    pub segment_size: usize,
+
+    /// Size in bytes for the index
+    pub index_size: usize,
+
+    /// Max amount of records per segment, when set the index is sized to hold exactly this
+    /// many entries and `index_size` is ignored
+    pub max_records_per_segment: Option<usize>,
+
+    /// Free bytes that must remain on the filesystem after creating a segment
+    pub min_free_space: u64,
+
+    /// Validation applied to every buffer before it's written
+    pub validation: Validation,
+
+    /// Expected size of the smallest records, when set the index is sized to hold as many of
+    /// them as the segment does (`index_size` is ignored). Each new segment is then sized from
+    /// the average record size of the previous one, when smaller.
+    pub expected_min_record_size: Option<usize>,
+
+    /// How entries are laid out on the index files
+    pub index_layout: IndexLayout,
+
+    /// What to do with segment files bigger than `segment_size`/`index_size`
+    pub oversized_segment: OversizedSegment,
+
+    /// When writes flush the active segment
+    pub flush_policy: FlushPolicy,
+
+    /// Flush the active segment once its unflushed bytes (log and index) reach this amount,
+    /// bounding what a crash can lose under bursty writes, `None` disables it
+    pub flush_dirty_bytes: Option<usize>,
+
+    /// Max duration of a flush before it's considered stuck, `None` disables the watchdog
+    pub flush_deadline: Option<Duration>,
+
+    /// What to do when a flush exceeds `flush_deadline`
+    pub stuck_flush: StuckFlush,
+
+    /// Re-read one in every N records of each segment in the background, straight from the
+    /// log file, to catch silent corruption early (see `CommitLog::sampling`), `None`
+    /// disables it
+    pub sample_every: Option<usize>,
+
+    /// Duration above which rotations and flushes are reported as `SlowOperation` events,
+    /// `None` disables them
+    pub slow_operation_threshold: Option<Duration>,
+
+    /// Called with every `SlowOperation` event, besides logging it
+    pub on_slow_operation: Option<fn(&SlowOperation)>,
+
+    /// How segment files are grouped into directories
+    pub bucketing: Bucketing,
+}
+
+impl Options {
+    /// Return the default options for the given sizes
+    pub fn new(segment_size: usize, index_size: usize) -> Self {
+        Self {
+            segment_size,
+            index_size,
+            max_records_per_segment: None,
+            min_free_space: 0,
+            validation: Validation::default(),
+            expected_min_record_size: None,
+            index_layout: IndexLayout::default(),
+            oversized_segment: OversizedSegment::default(),
+            flush_policy: FlushPolicy::default(),
+            flush_dirty_bytes: None,
+            flush_deadline: None,
+            stuck_flush: StuckFlush::default(),
+            sample_every: None,
+            slow_operation_threshold: None,
+            on_slow_operation: None,
+            bucketing: Bucketing::default(),
+        }
+    }
+
+    /// Return the default options for segments of `segment_size` bytes holding up to
+    /// `max_records` records each, sizing the index accordingly
+    pub fn with_max_records(segment_size: usize, max_records: usize) -> Self {
+        Self {
+            max_records_per_segment: Some(max_records),
+            ..Self::new(
+                segment_size,
+                index_size_for(max_records, IndexLayout::default()),
+            )
+        }
+    }
+
+    /// Size in bytes for the index, derived from `max_records_per_segment` or
+    /// `expected_min_record_size` when set
+    pub fn index_size(&self) -> usize {
+        match (self.max_records_per_segment, self.expected_min_record_size) {
+            (Some(records), _) => index_size_for(records, self.index_layout),
+            (None, Some(record_size)) => self.index_size_for_records_of(record_size),
+            (None, None) => self.index_size,
+        }
+    }
+
+    /// Size in bytes for an index holding a full segment of records of the given size
+    fn index_size_for_records_of(&self, record_size: usize) -> usize {
+        // records start after the header of the log
+        let records = self
+            .segment_size
+            .saturating_sub(LOG_HEADER_SIZE)
+            .div_ceil(record_size.max(1));
+        index_size_for(records, self.index_layout)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Position {
     /// The first entry available.
     Horizon,
     Offset(usize),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct Record {
     /// The current offset within current segment.
     pub current_offset: usize,
@@ -32,6 +277,74 @@ pub struct Record {
     pub segment_index: usize,
 }
 
+/// FilePosition
+///
+/// Where the bytes of a record live on disk, see `CommitLog::locate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePosition {
+    /// Path of the log file
+    pub path: PathBuf,
+    /// Position of the first byte of the record in the file
+    pub offset: usize,
+    /// Size of the record in bytes
+    pub len: usize,
+}
+
+/// Fence
+///
+/// A point of the log before which every record is durable, see `CommitLog::barrier`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fence {
+    /// Monotonically increasing id of the fence
+    pub id: u64,
+    /// Position the next record is written to, every record before it is durable
+    pub head: Record,
+}
+
+/// IoStats
+///
+/// A snapshot of the IO performed by the commit log since it was created.
+///
+/// Snapshots can be subtracted from each other to get the IO of a given interval, e.g.:
+/// ```ignore
+/// let before = clog.io_stats();
+/// // ... writes ...
+/// let interval = clog.io_stats() - before;
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct IoStats {
+    /// Bytes written to the memory maps (log and index)
+    pub bytes_written: usize,
+    /// Bytes written to the memory maps that were flushed afterwards
+    pub bytes_flushed: usize,
+    /// Amount of flushes issued to the memory maps
+    pub flushes: usize,
+}
+
+impl Add for IoStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes_written: self.bytes_written + other.bytes_written,
+            bytes_flushed: self.bytes_flushed + other.bytes_flushed,
+            flushes: self.flushes + other.flushes,
+        }
+    }
+}
+
+impl Sub for IoStats {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            bytes_written: self.bytes_written - other.bytes_written,
+            bytes_flushed: self.bytes_flushed - other.bytes_flushed,
+            flushes: self.flushes - other.flushes,
+        }
+    }
+}
+
 /// CommitLog
 ///
 /// The commit log is an abstraction that manages writes/reads to segments creating an append-only
@@ -72,17 +385,42 @@ pub struct CommitLog {
     /// Root directory for the Commitlog files
     path: PathBuf,
 
-    /// Size in bytes for the segments
-    segment_size: usize,
-
-    /// Size in bytes for the index
-    index_size: usize,
+    /// Settings given on creation
+    options: Options,
 
     /// List of segments
     segments: Vec<Segment>, //TODO if too many Segments are created, and not "garbage collected", we have too many files opened
 
     /// Current segment index
     current_segment: usize,
+
+    /// Latency of the append path, per stage
+    latency: Latency,
+
+    /// Watches over flushes, when `Options::flush_deadline` is set
+    watchdog: Option<Watchdog>,
+
+    /// Re-reads a sample of the records written, when `Options::sample_every` is set
+    sampler: Option<Sampler>,
+
+    /// Metadata attached to ranges of records
+    annotations: Annotations,
+
+    /// Persists the latest fence, see `barrier`
+    fences: Checkpoint,
+
+    /// Persists the settings and segments of the log, see `Manifest`
+    manifest: Checkpoint,
+
+    /// Records written to the active segment since it was last flushed by a write
+    unflushed_records: usize,
+
+    /// When the active segment was last flushed by a write (or created)
+    last_flush: Instant,
+
+    /// Keeps other CommitLogs of the process out of the directory, until dropped, `None` when
+    /// opened read-only
+    registration: Option<Registration>,
 }
 
 impl CommitLog {
@@ -91,37 +429,304 @@ impl CommitLog {
         segment_size: usize,
         index_size: usize,
     ) -> Result<Self, Error> {
+        Self::with_options(path, Options::new(segment_size, index_size))
+    }
+
+    /// Open the commit log in the given directory, creating it if needed
+    ///
+    /// Segments already in the directory are reopened, and writes continue after the last
+    /// record of the last one.
+    pub fn with_options<P: Into<PathBuf>>(path: P, options: Options) -> Result<Self, Error> {
         let path = path.into();
         if !path.as_path().exists() {
             fs::create_dir_all(path.clone())?;
         }
 
-        let segments = vec![Segment::new(path.clone(), 0, segment_size, index_size)?];
+        // read-only opens (`CommitLog::open_read_only`) don't register, and are allowed
+        let registration = Registration::register(&path)?.ok_or(Error::AlreadyOpen)?;
+
+        let watchdog = match options.flush_deadline {
+            Some(deadline) => Some(Watchdog::spawn(deadline, options.stuck_flush)?),
+            None => None,
+        };
+
+        let sampler = match options.sample_every {
+            Some(_) => Some(Sampler::spawn()?),
+            None => None,
+        };
+
+        let manifest = Checkpoint::open(path.join("manifest"))?;
+        let stored = match manifest.load()? {
+            Some(payload) => {
+                Some(Manifest::from_bytes(&payload).ok_or(Error::UnsupportedManifest)?)
+            }
+            None => None,
+        };
+        if let Some(stored) = &stored {
+            let expected = Manifest::new(&options, Vec::new());
+            if !stored.is_compatible(&expected, options.oversized_segment) {
+                return Err(Error::ManifestMismatch(stored.clone()));
+            }
+        }
+
+        let mut clog = Self {
+            annotations: Annotations::open(&path)?,
+            fences: Checkpoint::open(path.join("fence"))?,
+            manifest,
+            registration: Some(registration),
+            path,
+            segments: Vec::new(),
+            options,
+            current_segment: 0,
+            latency: Latency::default(),
+            watchdog,
+            sampler,
+            unflushed_records: 0,
+            last_flush: Instant::now(),
+        };
+
+        clog.reopen()?;
+        // listed segments are gone or moved, e.g. by hand
+        if let Some(stored) = stored {
+            if let Some(offset) = stored.missing(&clog.segment_dirs()) {
+                return Err(Error::MissingSegment(offset));
+            }
+        }
+        if clog.segments.is_empty() {
+            clog.preflight()?;
+            let segment = clog.new_segment(clog.bucket()?, 0)?;
+            clog.segments.push(segment);
+        }
+        clog.store_manifest()?;
+
+        Ok(clog)
+    }
+
+    /// Open the commit log in the given directory for reading only
+    ///
+    /// Nothing in the directory is created or modified, and the directory isn't registered, so
+    /// a log being written by another CommitLog (of this process or not) can be opened, e.g. to
+    /// `diff` it. Segments are opened with `Segment::open_read_only` and only hold the records
+    /// written by then. Writes, bulk loads, barriers and annotations fail with
+    /// `Error::ReadOnly`.
+    ///
+    /// The settings (e.g. the index layout) are the ones of the manifest, a log of formats
+    /// this version can't read fails with `Error::ManifestMismatch`.
+    pub fn open_read_only<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+
+        let manifest = Checkpoint::open(path.join("manifest"))?;
+        let stored = match manifest.load()? {
+            Some(payload) => {
+                Some(Manifest::from_bytes(&payload).ok_or(Error::UnsupportedManifest)?)
+            }
+            None => None,
+        };
+        let options = match &stored {
+            Some(stored) => {
+                let mut options = Options::new(stored.segment_size, stored.index_size);
+                options.index_layout = stored.index_layout;
+                // the formats must be readable, segments are opened at whatever size they are
+                let expected = Manifest::new(&options, Vec::new());
+                if !stored.is_compatible(&expected, OversizedSegment::Adopt) {
+                    return Err(Error::ManifestMismatch(stored.clone()));
+                }
+                options
+            }
+            None => Options::new(0, 0),
+        };
+
+        let mut segments = Vec::new();
+        for (i, (offset, dir)) in layout::find_segments(&path)?.into_iter().enumerate() {
+            if offset < i {
+                return Err(Error::DuplicateSegment(offset));
+            }
+            if offset != i {
+                return Err(Error::MissingSegment(i));
+            }
 
-        Ok(Self {
+            segments.push(Segment::open_read_only(
+                dir.join(format!("{:020}.log", offset)),
+                dir.join(format!("{:020}.idx", offset)),
+                options.index_layout,
+            )?);
+        }
+
+        let clog = Self {
+            annotations: Annotations::open_read_only(&path)?,
+            fences: Checkpoint::open(path.join("fence"))?,
+            manifest,
+            registration: None,
             path,
             segments,
-            segment_size,
-            index_size,
+            options,
             current_segment: 0,
-        })
+            latency: Latency::default(),
+            watchdog: None,
+            sampler: None,
+            unflushed_records: 0,
+            last_flush: Instant::now(),
+        };
+
+        if let Some(stored) = stored {
+            if let Some(offset) = stored.missing(&clog.segment_dirs()) {
+                return Err(Error::MissingSegment(offset));
+            }
+        }
+        if clog.segments.is_empty() {
+            return Err(Error::MissingSegment(0));
+        }
+
+        Ok(clog)
+    }
+
+    /// Return true if the log was opened read-only, see `open_read_only`
+    pub fn is_read_only(&self) -> bool {
+        self.registration.is_none()
+    }
+
+    /// Set the amount of free bytes that must remain on the filesystem after a new segment is
+    /// created, rotations fail with `Error::InsufficientDiskSpace` otherwise.
+    pub fn set_min_free_space(&mut self, bytes: u64) {
+        self.options.min_free_space = bytes;
+    }
+
+    /// Set when writes flush the active segment, see `FlushPolicy`
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.options.flush_policy = policy;
+    }
+
+    /// Set the validation applied to every buffer before it's written, invalid buffers are
+    /// rejected with `Error::Validation`.
+    pub fn set_validation(&mut self, validation: Validation) {
+        self.options.validation = validation;
     }
 
     pub fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
         let buffer_size = buffer.len();
 
-        if buffer_size > self.segment_size {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+        if self.is_degraded() {
+            return Err(Error::Degraded);
+        }
+
+        // records never fit a segment past the room left by the log header, not even an empty
+        // one, fail before rotating to it
+        let capacity = self.options.segment_size.saturating_sub(LOG_HEADER_SIZE);
+        if buffer_size > MAX_PAYLOAD_SIZE || frame_size(buffer_size) > capacity {
             return Err(Error::BufferSizeExceeded);
         }
 
-        if !self.active_segment().fit(buffer_size) {
+        self.options.validation.validate(buffer)?;
+
+        let start = self.latency.start();
+        let max_records = self.options.max_records_per_segment;
+        let segment = self.active_segment();
+        let full = max_records.is_some_and(|max| segment.entries() >= max);
+        let fit = !full && segment.fit(buffer_size);
+        self.latency.record(Stage::Fit, start);
+
+        if !fit {
+            let start = self.latency.start();
+            let started = Instant::now();
+            let sealed = self.active_segment().io_stats().bytes_written;
             self.rotate_segment()?;
+            self.latency.record(Stage::Rotation, start);
+
+            let cause = if full {
+                "max records per segment"
+            } else {
+                "segment full"
+            };
+            self.report_slow(Operation::Rotation, started, sealed, cause);
+        }
+
+        let active = self.segments.len() - 1;
+        let len = self.segments[active].write(buffer, &mut self.latency)?;
+        self.unflushed_records += 1;
+
+        if let (Some(sampler), Some(every)) = (&self.sampler, self.options.sample_every) {
+            let record = Record {
+                segment_index: active,
+                current_offset: self.segments[active].entries() - 1,
+            };
+            // best-effort, the record is written already: a failure mustn't fail the write
+            if record.current_offset.is_multiple_of(every.max(1)) {
+                match self.locate(&record) {
+                    Ok(position) => sampler.sample(record, position),
+                    Err(e) => log::warn!("could not sample record {:?}: {:?}", record, e),
+                }
+            }
+        }
+
+        let stats = self.segments[active].io_stats();
+        let dirty = stats.bytes_written - stats.bytes_flushed;
+        let cause = match self.options.flush_policy {
+            FlushPolicy::Manual => None,
+            FlushPolicy::EveryWrite => Some("flush policy (every write)"),
+            FlushPolicy::EveryRecords(records) if self.unflushed_records >= records => {
+                Some("flush policy (every records)")
+            }
+            FlushPolicy::Interval(interval) if self.last_flush.elapsed() >= interval => {
+                Some("flush policy (interval)")
+            }
+            _ => None,
+        };
+        let cause = cause.or_else(|| {
+            self.options
+                .flush_dirty_bytes
+                .filter(|threshold| dirty >= *threshold)
+                .map(|_| "dirty bytes threshold")
+        });
+
+        if let Some(cause) = cause {
+            let started = Instant::now();
+            let guard = self.watchdog.as_ref().map(Watchdog::arm);
+            self.segments[active].flush()?;
+            drop(guard);
+            self.unflushed_records = 0;
+            self.last_flush = Instant::now();
+            self.report_slow(Operation::Flush, started, dirty, cause);
         }
 
-        let len = self.active_segment().write(buffer)?;
         Ok(len)
     }
 
+    /// Write the buffer only if no other record was appended since `expected_head`.
+    ///
+    /// Enables optimistic concurrency control: read the `head`, decide what to append, and
+    /// append it only if the log didn't move in the meantime, otherwise `Error::HeadMismatch`.
+    ///
+    /// Returns the position of the appended record.
+    pub fn append_if_head(
+        &mut self,
+        expected_head: &Record,
+        buffer: &[u8],
+    ) -> Result<Record, Error> {
+        if self.head() != *expected_head {
+            return Err(Error::HeadMismatch);
+        }
+
+        self.write(buffer)?;
+
+        Ok(Record {
+            segment_index: self.segments.len() - 1,
+            current_offset: self.active_segment().entries() - 1,
+        })
+    }
+
+    /// Return the position the next record would be written to on the current segment
+    pub fn head(&self) -> Record {
+        let segment_index = self.segments.len() - 1;
+
+        Record {
+            segment_index,
+            current_offset: self.segments[segment_index].entries(),
+        }
+    }
+
     pub fn read_at(&mut self, segment_index: usize, offset: usize) -> Result<&[u8], Error> {
         if segment_index >= self.segments.len() {
             return Err(Error::SegmentUnavailable);
@@ -131,6 +736,70 @@ impl CommitLog {
         Ok(buf)
     }
 
+    /// Return where the record lives on disk, so external tools (e.g. sendfile, tracing,
+    /// forensics) can work with the raw files.
+    ///
+    /// The bytes are written through a memory map, only after `flush_until` (or `barrier`)
+    /// covering the record are they guaranteed to be in the file for other readers.
+    pub fn locate(&self, record: &Record) -> Result<FilePosition, Error> {
+        if record.segment_index >= self.segments.len() {
+            return Err(Error::SegmentUnavailable);
+        }
+
+        let segment = &self.segments[record.segment_index];
+        let (offset, len) = segment.locate(record.current_offset)?;
+        Ok(FilePosition {
+            path: segment.path().join(segment.log_file_name()),
+            offset,
+            len,
+        })
+    }
+
+    /// Read up to `count` consecutive records of a segment, starting at `offset`
+    pub fn read_many(
+        &self,
+        segment_index: usize,
+        offset: usize,
+        count: usize,
+    ) -> Result<Vec<&[u8]>, Error> {
+        if segment_index >= self.segments.len() {
+            return Err(Error::SegmentUnavailable);
+        }
+
+        let bufs = self.segments[segment_index].read_many(offset, count)?;
+        Ok(bufs)
+    }
+
+    /// Fetch the records starting at `record` as a single contiguous slice of up to
+    /// `max_bytes`, see `Reader::fetch`
+    pub fn fetch(&self, record: &Record, max_bytes: usize) -> Result<Fetch<'_>, Error> {
+        Ok(Reader::new(self).fetch(record, max_bytes)?)
+    }
+
+    /// Poll a batch of records starting at `record`, copied out of the log, see
+    /// `Reader::poll_batch`
+    pub fn poll_batch(
+        &self,
+        record: &Record,
+        max_records: usize,
+        max_bytes: usize,
+        time_budget: Duration,
+    ) -> Result<Batch, Error> {
+        Ok(Reader::new(self).poll_batch(record, max_records, max_bytes, time_budget)?)
+    }
+
+    /// Return an iterator over every record of the log, in order
+    pub fn iter(&self) -> Iter<'_> {
+        self.iter_from(&Position::Horizon)
+    }
+
+    /// Return an iterator over the records of the log, starting at the given position
+    ///
+    /// `Position::Offset` counts records from the beginning of the log, across segments.
+    pub fn iter_from(&self, position: &Position) -> Iter<'_> {
+        Iter::new(&self.segments, position)
+    }
+
     pub fn read_after(&mut self, position: &Position, mut offset: usize) -> Result<Record, Error> {
         let horizon: usize = 1;
         let current_pos = match position {
@@ -149,102 +818,1387 @@ impl CommitLog {
         self.read_after(position, 0)
     }
 
-    fn rotate_segment(&mut self) -> Result<(), Error> {
-        let next_offset = self.segments.len();
+    /// Guarantee the durability of every record up to (and including) the given position.
+    ///
+    /// Only the ranges not yet on disk are flushed, which is cheaper than flushing whole
+    /// segments when just a few records (e.g. checkpoint markers) need to be durable.
+    pub fn flush_until(&mut self, record: &Record) -> Result<(), Error> {
+        if record.segment_index >= self.segments.len() {
+            return Err(Error::SegmentUnavailable);
+        }
+
+        let started = Instant::now();
+        let flushed = self.io_stats().bytes_flushed;
+        let guard = self.watchdog.as_ref().map(Watchdog::arm);
+        for segment in &mut self.segments[0..record.segment_index] {
+            segment.sync()?;
+        }
+        self.segments[record.segment_index].flush_until(record.current_offset)?;
+        drop(guard);
 
-        self.active_segment().flush()?;
+        let bytes = self.io_stats().bytes_flushed - flushed;
+        self.report_slow(Operation::Flush, started, bytes, "flush_until");
+        Ok(())
+    }
 
-        self.segments.push(Segment::new(
-            self.path.clone(),
-            next_offset,
-            self.segment_size,
-            self.index_size,
-        )?);
+    /// Durably attach metadata to the records from `from` to `to` (inclusive), leaving the
+    /// records untouched. Readers expose it via `Reader::annotations`.
+    pub fn annotate(&mut self, from: &Record, to: &Record, data: &[u8]) -> Result<(), Error> {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
 
+        self.annotations.append(Annotation {
+            from: from.clone(),
+            to: to.clone(),
+            data: data.to_vec(),
+        })?;
         Ok(())
     }
 
-    fn active_segment(&mut self) -> &mut Segment {
-        let index = self.segments.len() - 1;
-        &mut self.segments[index]
+    /// Durably flush every record appended so far, then persist a new fence at the head.
+    ///
+    /// Once it returns, every record before the fence is durable and visible to readers, so
+    /// embedders can rely on the fence id when implementing their checkpoint protocols.
+    pub fn barrier(&mut self) -> Result<Fence, Error> {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        let started = Instant::now();
+        let flushed = self.io_stats().bytes_flushed;
+        let guard = self.watchdog.as_ref().map(Watchdog::arm);
+        for segment in &mut self.segments {
+            segment.sync()?;
+        }
+        drop(guard);
+
+        let bytes = self.io_stats().bytes_flushed - flushed;
+        self.report_slow(Operation::Flush, started, bytes, "barrier");
+
+        let head = self.head();
+        let id = self.fences.store(&head.encode(Encoding::Binary))?;
+        Ok(Fence { id, head })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    extern crate tempfile;
-    use super::*;
-    use std::fs;
-    use std::path::Path;
-    use tempfile::tempdir;
+    /// Return the latest fence, `None` if no barrier was ever issued
+    pub fn last_fence(&self) -> Result<Option<Fence>, Error> {
+        let payload = match self.fences.load()? {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
 
-    #[test]
-    #[should_panic]
-    fn test_invalid_create() {
-        CommitLog::new(Path::new("\0").to_path_buf(), 100, 10000).unwrap();
+        let head = Record::decode_persisted(&payload).map_err(checkpoint::Error::Codec)?;
+        Ok(Some(Fence {
+            id: self.fences.generation(),
+            head,
+        }))
     }
 
-    #[test]
-    fn test_create() {
-        // create folder
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        CommitLog::new(tmp_dir.clone(), 100, 1000).unwrap();
-        assert!(tmp_dir.as_path().exists());
+    /// Return a snapshot of the IO performed across all segments
+    pub fn io_stats(&self) -> IoStats {
+        self.segments
+            .iter()
+            .fold(IoStats::default(), |stats, segment| {
+                stats + segment.io_stats()
+            })
+    }
 
-        // accept an existing folder
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        fs::create_dir_all(tmp_dir.clone()).unwrap();
-        CommitLog::new(tmp_dir.clone(), 100, 1000).unwrap();
-        assert!(tmp_dir.as_path().exists());
+    /// Return the latency histograms of the append path, per stage
+    ///
+    /// The report can be printed as a table of percentiles, or its histograms inspected directly.
+    #[cfg(feature = "latency")]
+    pub fn latency_report(&self) -> &Latency {
+        &self.latency
     }
 
-    #[test]
-    fn test_write() {
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 100, 1000).unwrap();
+    /// Return the counters of the records re-read in the background, `None` unless
+    /// `Options::sample_every` is set
+    pub fn sampling(&self) -> Option<Sampling> {
+        self.sampler.as_ref().map(Sampler::sampling)
+    }
 
-        assert_eq!(c.write(b"this-has-less-than-100-bytes").unwrap(), 28);
+    /// Return true if a flush exceeded `Options::flush_deadline`, writes are refused from then on
+    pub fn is_degraded(&self) -> bool {
+        self.watchdog.as_ref().is_some_and(Watchdog::is_degraded)
     }
 
-    #[test]
-    fn test_write_rotate_segments() {
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 100, 1000).unwrap();
-        c.write(
-            b"this-should-have-about-80-bytes-but-not-really-sure-to-be-honest-maybe-it-doesn't",
-        )
-        .unwrap();
+    /// Bulk-load pre-partitioned records, much faster than appending them one by one.
+    ///
+    /// Each partition is written to its own segment by its own thread, in a staging directory.
+    /// Once every partition is written and flushed, the segment files are moved into the log
+    /// directory and the segments appended to the log in order, the last one becoming the
+    /// active segment. If any partition fails (e.g. doesn't fit a segment), or moving them does,
+    /// nothing is added.
+    ///
+    /// Returns the head of the log after the load.
+    pub fn bulk_load(&mut self, partitions: &[Vec<&[u8]>]) -> Result<Record, Error> {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+        if self.is_degraded() {
+            return Err(Error::Degraded);
+        }
 
-        // it should 'fail' since the segment has only 100 bytes, but this triggers a rotation
-        assert_eq!(c.write(b"a-bit-more-than-20-bytes").unwrap(), 24);
+        let base_offset = self.segments.len();
+        let staging = self.path.join(".bulk");
+        // left behind by a crash during a previous load
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+
+        let this = &*self;
+        let results: Vec<Result<Segment, Error>> = thread::scope(|scope| {
+            let workers: Vec<_> = partitions
+                .iter()
+                .enumerate()
+                .map(|(i, records)| {
+                    let staging = staging.clone();
+                    scope.spawn(move || this.load_partition(staging, base_offset + i, records))
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("bulk load worker panicked"))
+                .collect()
+        });
+
+        let segments = match results
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|segments| self.promote(segments, &staging, base_offset))
+        {
+            Ok(segments) => segments,
+            Err(e) => {
+                fs::remove_dir_all(&staging)?;
+                return Err(e);
+            }
+        };
+        self.segments.extend(segments);
+        // only the empty staging directory is left, it's wiped by the next load otherwise
+        fs::remove_dir_all(&staging)?;
+        self.store_manifest()?;
+
+        Ok(self.head())
     }
 
-    #[test]
-    #[should_panic]
-    fn test_invalid_write() {
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 10, 10000).unwrap();
+    /// Move the staged segments of a bulk load into the log directory
+    ///
+    /// Every segment but the last one is sealed first, as is the active segment of the log
+    /// once they are all moved. On failure the files already moved are removed again, so none
+    /// of them is mistaken for a segment of the log later on.
+    fn promote(
+        &mut self,
+        mut segments: Vec<Segment>,
+        staging: &Path,
+        base_offset: usize,
+    ) -> Result<Vec<Segment>, Error> {
+        let sealed = segments.len().saturating_sub(1);
+        for segment in segments.iter_mut().take(sealed) {
+            segment.seal()?;
+        }
 
-        // it should fail since the buffer is bigger than the max size of the segment
-        c.write(b"the-buffer-is-too-big").unwrap();
+        // the open segments keep their file descriptors across the rename
+        let bucket = self.bucket()?;
+        let mut moved = Vec::new();
+        let mut result = (|| -> Result<(), Error> {
+            for i in 0..segments.len() {
+                let extensions: &[&str] = if i < sealed {
+                    &["log", "idx", "ftr"]
+                } else {
+                    &["log", "idx"]
+                };
+                for extension in extensions {
+                    let name = format!("{:020}.{}", base_offset + i, extension);
+                    fs::rename(staging.join(&name), bucket.join(&name))?;
+                    moved.push(name);
+                }
+            }
+            File::open(&bucket)?.sync_all()?;
+
+            Ok(())
+        })();
+
+        if result.is_ok() {
+            result = if segments.is_empty() {
+                self.active_segment().flush()
+            } else {
+                self.active_segment().seal()
+            }
+            .map_err(Error::from);
+        }
+        if let Err(e) = result {
+            for name in &moved {
+                let _ = fs::remove_file(bucket.join(name));
+            }
+            return Err(e);
+        }
+
+        for segment in &mut segments {
+            segment.relocate(bucket.clone());
+        }
+
+        Ok(segments)
     }
 
-    #[test]
-    fn test_read() {
-        let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 50, 10000).unwrap();
+    /// Write a partition of a bulk load to a new segment in the given directory
+    fn load_partition(
+        &self,
+        path: PathBuf,
+        offset: usize,
+        records: &[&[u8]],
+    ) -> Result<Segment, Error> {
+        self.preflight()?;
+        let mut segment = self.new_segment(path, offset)?;
+        let mut latency = Latency::default();
 
-        c.write(b"this-has-less-20b").unwrap();
-        c.write(b"second-record").unwrap();
-        // segment switch trigger
-        c.write(b"third-record-bigger-goes-to-another-segment")
-            .unwrap();
+        for record in records {
+            let full = self
+                .options
+                .max_records_per_segment
+                .is_some_and(|max| segment.entries() >= max);
+            if full || !segment.fit(record.len()) {
+                return Err(Error::BufferSizeExceeded);
+            }
 
-        assert_eq!(c.read_at(0, 0).unwrap(), "this-has-less-20b".as_bytes());
-        assert_eq!(c.read_at(0, 1).unwrap(), "second-record".as_bytes());
+            self.options.validation.validate(record)?;
+            segment.write(record, &mut latency)?;
+        }
+        segment.sync()?;
+
+        Ok(segment)
+    }
+
+    /// Verify the invariants of every segment, see `Segment::verify`
+    pub fn verify(&self) -> Result<(), Error> {
+        for segment in &self.segments {
+            segment.verify()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reopen the segments already in the directory, or its buckets, in order
+    ///
+    /// Every segment but the last one is sealed, the last one becomes the active segment and
+    /// is written right after its last record.
+    fn reopen(&mut self) -> Result<(), Error> {
+        for (i, (offset, path)) in layout::find_segments(&self.path)?.into_iter().enumerate() {
+            if offset < i {
+                return Err(Error::DuplicateSegment(offset));
+            }
+            if offset != i {
+                return Err(Error::MissingSegment(i));
+            }
+
+            // the index size depends on the records written so far, keep the one it was
+            // created with if bigger
+            let index_path = path.join(format!("{:020}.idx", offset));
+            let index_size = fs::metadata(index_path)
+                .map_or(0, |metadata| metadata.len() as usize)
+                .max(self.index_size());
+
+            if let Some(previous) = self.segments.last_mut() {
+                if !previous.is_sealed() {
+                    previous.seal()?;
+                }
+            }
+            let segment = Segment::new(
+                path,
+                offset,
+                self.options.segment_size,
+                index_size,
+                self.options.oversized_segment,
+                self.options.index_layout,
+            )?;
+            self.segments.push(segment);
+        }
+
+        Ok(())
+    }
+
+    fn rotate_segment(&mut self) -> Result<(), Error> {
+        let next_offset = self.segments.len();
+
+        // sealed segments are never written again, catch inconsistencies before moving on
+        #[cfg(debug_assertions)]
+        self.active_segment().verify()?;
+
+        let guard = self.watchdog.as_ref().map(Watchdog::arm);
+        self.active_segment().seal()?;
+        drop(guard);
+        self.preflight()?;
+
+        let segment = self.new_segment(self.bucket()?, next_offset)?;
+        self.segments.push(segment);
+        self.store_manifest()?;
+        self.unflushed_records = 0;
+        self.last_flush = Instant::now();
+
+        Ok(())
+    }
+
+    /// Emit a `SlowOperation` event if the operation started at `started` took too long
+    fn report_slow(
+        &self,
+        operation: Operation,
+        started: Instant,
+        bytes: usize,
+        cause: &'static str,
+    ) {
+        let threshold = match self.options.slow_operation_threshold {
+            Some(threshold) => threshold,
+            None => return,
+        };
+
+        let duration = started.elapsed();
+        if duration < threshold {
+            return;
+        }
+
+        log::warn!(
+            target: "commit_log::telemetry",
+            "slow {:?} in {:?}: duration={:?} bytes={} cause={}",
+            operation,
+            self.path,
+            duration,
+            bytes,
+            cause
+        );
+
+        if let Some(hook) = self.options.on_slow_operation {
+            hook(&SlowOperation {
+                operation,
+                duration,
+                bytes,
+                cause,
+            });
+        }
+    }
+
+    /// Ensure the filesystem has room for a new segment, before any of its files is created
+    fn preflight(&self) -> Result<(), Error> {
+        let required = ((self.options.segment_size + self.index_size()) as u64)
+            .saturating_add(self.options.min_free_space);
+        let available = disk::available_space(&self.path)?;
+
+        if available < required {
+            log::warn!(
+                "refusing to create a segment in {:?}: {} bytes available, {} required",
+                self.path,
+                available,
+                required
+            );
+            return Err(Error::InsufficientDiskSpace);
+        }
+
+        Ok(())
+    }
+
+    /// Size in bytes for the index of the next segment
+    ///
+    /// With `expected_min_record_size`, records of the active segment smaller on average than
+    /// expected grow the next index, so it doesn't fill before the log.
+    fn index_size(&self) -> usize {
+        let (expected, segment) = match (
+            self.options.max_records_per_segment,
+            self.options.expected_min_record_size,
+            self.segments.last(),
+        ) {
+            (None, Some(expected), Some(segment)) if segment.entries() > 0 => (expected, segment),
+            _ => return self.options.index_size(),
+        };
+
+        let average = (segment.size() - LOG_HEADER_SIZE) / segment.entries();
+        self.options
+            .index_size_for_records_of(expected.min(average))
+    }
+
+    /// Return the offset of every segment along with its directory, relative to the root
+    fn segment_dirs(&self) -> Vec<(usize, PathBuf)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(offset, segment)| {
+                let dir = segment
+                    .path()
+                    .strip_prefix(&self.path)
+                    .unwrap_or(segment.path());
+                (offset, dir.to_path_buf())
+            })
+            .collect()
+    }
+
+    /// Durably record the current settings and segments of the log, see `Manifest`
+    fn store_manifest(&mut self) -> Result<(), Error> {
+        let manifest = Manifest::new(&self.options, self.segment_dirs());
+        self.manifest.store(&manifest.to_bytes())?;
+        Ok(())
+    }
+
+    /// Return the directory for a segment created now, creating it if needed
+    fn bucket(&self) -> Result<PathBuf, Error> {
+        let path = self.options.bucketing.dir(&self.path, SystemTime::now());
+        if !path.exists() {
+            fs::create_dir_all(&path)?;
+            File::open(&self.path)?.sync_all()?;
+        }
+
+        Ok(path)
+    }
+
+    fn new_segment(&self, path: PathBuf, offset: usize) -> Result<Segment, Error> {
+        let segment = Segment::new(
+            path,
+            offset,
+            self.options.segment_size,
+            self.index_size(),
+            self.options.oversized_segment,
+            self.options.index_layout,
+        )?;
+        Ok(segment)
+    }
+
+    fn active_segment(&mut self) -> &mut Segment {
+        let index = self.segments.len() - 1;
+        &mut self.segments[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_create() {
+        CommitLog::new(Path::new("\0").to_path_buf(), 100, 10000).unwrap();
+    }
+
+    #[test]
+    fn test_create() {
+        // create folder
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        CommitLog::new(tmp_dir.clone(), 100, 1000).unwrap();
+        assert!(tmp_dir.as_path().exists());
+
+        // accept an existing folder
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        CommitLog::new(tmp_dir.clone(), 100, 1000).unwrap();
+        assert!(tmp_dir.as_path().exists());
+    }
+
+    #[test]
+    fn test_already_open() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let c = CommitLog::new(tmp_dir.clone(), 100, 1000).unwrap();
+
+        match CommitLog::new(tmp_dir.join("."), 100, 1000) {
+            Err(Error::AlreadyOpen) => {}
+            _ => panic!("expected Error::AlreadyOpen"),
+        }
+
+        // available again once closed
+        drop(c);
+        CommitLog::new(tmp_dir, 100, 1000).unwrap();
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        c.barrier().unwrap();
+
+        // alongside the writer
+        let mut r = CommitLog::open_read_only(tmp_dir.clone()).unwrap();
+        assert!(r.is_read_only());
+        assert!(diff(&c, &r).unwrap().is_identical());
+        assert_eq!(r.head(), c.head());
+        assert_eq!(
+            r.read_at(1, 0).unwrap(),
+            b"third-record-bigger-goes-to-another-segment"
+        );
+
+        assert!(matches!(r.write(b"fourth"), Err(Error::ReadOnly)));
+        assert!(matches!(r.barrier(), Err(Error::ReadOnly)));
+        assert!(matches!(
+            r.bulk_load(&[vec![&b"fourth"[..]]]),
+            Err(Error::ReadOnly)
+        ));
+        let record = r.head();
+        assert!(matches!(
+            r.annotate(&record, &record, b"quarantined"),
+            Err(Error::ReadOnly)
+        ));
+        assert_eq!(r.segments.len(), 2);
+
+        // records written afterwards aren't seen
+        c.write(b"fourth").unwrap();
+        assert_eq!(r.head().current_offset, 1);
+
+        // nor are logs of formats it can't read
+        let mut manifest = Manifest::new(&c.options, c.segment_dirs());
+        manifest.log_version += 1;
+        c.manifest.store(&manifest.to_bytes()).unwrap();
+        assert!(matches!(
+            CommitLog::open_read_only(tmp_dir.clone()),
+            Err(Error::ManifestMismatch(_))
+        ));
+
+        // nothing to open
+        let empty = tempdir().unwrap();
+        assert!(matches!(
+            CommitLog::open_read_only(empty.path()),
+            Err(Error::MissingSegment(0))
+        ));
+    }
+
+    #[test]
+    fn test_write() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 100, 1000).unwrap();
+
+        assert_eq!(c.write(b"this-has-less-than-100-bytes").unwrap(), 28);
+    }
+
+    #[test]
+    fn test_write_rotate_segments() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 132, 1000).unwrap();
+        c.write(
+            b"this-should-have-about-80-bytes-but-not-really-sure-to-be-honest-maybe-it-doesn't",
+        )
+        .unwrap();
+
+        // it should 'fail' since the segment has only 100 bytes left, but this triggers a rotation
+        assert_eq!(c.write(b"a-bit-more-than-20-bytes").unwrap(), 24);
+
+        // the previous segment is sealed, with a footer
+        assert!(c.path.join("00000000000000000000.ftr").exists());
+        assert!(!c.path.join("00000000000000000001.ftr").exists());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_write() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 10, 10000).unwrap();
+
+        // it should fail since the buffer is bigger than the max size of the segment
+        c.write(b"the-buffer-is-too-big").unwrap();
+    }
+
+    #[test]
+    fn test_write_boundary() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+
+        // 60 bytes left after the log header, a record one byte too big is refused without
+        // rotating
+        let too_big = [b'x'; 60 - 8 + 1];
+        for _ in 0..3 {
+            assert!(matches!(c.write(&too_big), Err(Error::BufferSizeExceeded)));
+        }
+        assert_eq!(c.segments.len(), 1);
+
+        // one filling a whole segment goes to the next one
+        c.write(&too_big[1..]).unwrap();
+        assert_eq!(c.segments.len(), 2);
+        assert_eq!(c.read_at(1, 0).unwrap(), &too_big[1..]);
+    }
+
+    #[test]
+    fn test_io_stats() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+        assert_eq!(
+            c.io_stats(),
+            IoStats {
+                bytes_written: 32 + 32, // the headers of the log and the index
+                bytes_flushed: 0,
+                flushes: 0,
+            }
+        );
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        let before = c.io_stats();
+        assert_eq!(
+            before,
+            IoStats {
+                bytes_written: 142, // both headers + 46 bytes of records + 2 index entries
+                bytes_flushed: 0,
+                flushes: 0,
+            }
+        );
+
+        // segment switch trigger, which flushes the previous segment
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        assert_eq!(
+            c.io_stats() - before,
+            IoStats {
+                bytes_written: 32 + 51 + 32 + 16,
+                bytes_flushed: 142,
+                flushes: 2, // log and index
+            }
+        );
+    }
+
+    #[test]
+    fn test_append_if_head() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        let head = c.head();
+        assert_eq!(
+            head,
+            Record {
+                segment_index: 0,
+                current_offset: 0
+            }
+        );
+        let record = c.append_if_head(&head, b"this-has-less-20b").unwrap();
+        assert_eq!(record, head);
+
+        // another writer appended in the meantime
+        c.write(b"second-record").unwrap();
+        match c.append_if_head(&head, b"conflict") {
+            Err(Error::HeadMismatch) => {}
+            _ => panic!("expected Error::HeadMismatch"),
+        }
+
+        // appending at the current head works, even when it triggers a segment switch
+        let head = c.head();
+        let record = c
+            .append_if_head(&head, b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        assert_eq!(
+            record,
+            Record {
+                segment_index: 1,
+                current_offset: 0
+            }
+        );
         assert_eq!(
             c.read_at(1, 0).unwrap(),
             "third-record-bigger-goes-to-another-segment".as_bytes()
         );
     }
+
+    #[test]
+    fn test_insufficient_disk_space() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+        c.set_min_free_space(u64::MAX);
+
+        c.write(b"this-has-less-20b").unwrap();
+        match c.write(b"third-record-bigger-goes-to-another-segment") {
+            Err(Error::InsufficientDiskSpace) => {}
+            _ => panic!("expected Error::InsufficientDiskSpace"),
+        }
+
+        // no segment was created
+        assert!(!tmp_dir.join("00000000000000000001.log").exists());
+        assert!(!tmp_dir.join("00000000000000000001.idx").exists());
+    }
+
+    #[test]
+    fn test_page_aligned_index() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::with_max_records(1000, 2);
+        options.index_layout = IndexLayout::PageAligned;
+        let mut c = CommitLog::with_options(tmp_dir.clone(), options).unwrap();
+
+        // the index takes a whole page, but still holds 2 records only
+        c.write(b"first").unwrap();
+        c.write(b"second").unwrap();
+        c.write(b"third").unwrap();
+
+        assert_eq!(c.segments.len(), 2);
+        assert_eq!(c.read_at(0, 1).unwrap(), b"second");
+        assert_eq!(c.read_at(1, 0).unwrap(), b"third");
+
+        // read-only, with the layout of the manifest
+        c.barrier().unwrap();
+        let r = CommitLog::open_read_only(tmp_dir).unwrap();
+        assert_eq!(r.options.index_layout, IndexLayout::PageAligned);
+        assert!(diff(&c, &r).unwrap().is_identical());
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+
+        let partitions = vec![
+            vec!["first".as_bytes(), "second".as_bytes()],
+            vec!["third".as_bytes()],
+        ];
+        let head = c.bulk_load(&partitions).unwrap();
+
+        assert_eq!(
+            head,
+            Record {
+                segment_index: 2,
+                current_offset: 1,
+            }
+        );
+        assert_eq!(c.read_at(0, 0).unwrap(), b"this-has-less-20b");
+        assert_eq!(c.read_at(1, 1).unwrap(), b"second");
+        assert_eq!(c.read_at(2, 0).unwrap(), b"third");
+        assert!(tmp_dir.join("00000000000000000002.log").exists());
+        assert!(!tmp_dir.join(".bulk").exists());
+
+        // the last loaded segment is the active one
+        c.write(b"fourth").unwrap();
+        assert_eq!(c.read_at(2, 1).unwrap(), b"fourth");
+
+        // a partition not fitting a segment loads nothing
+        let partitions = vec![vec!["this-partition-is-bigger-than-50-bytes".as_bytes(); 2]];
+        assert!(matches!(
+            c.bulk_load(&partitions),
+            Err(Error::BufferSizeExceeded)
+        ));
+        assert_eq!(c.segments.len(), 3);
+        assert!(!tmp_dir.join(".bulk").exists());
+    }
+
+    #[test]
+    fn test_bulk_load_failed_move() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+
+        // a leftover of a crashed load is wiped
+        fs::create_dir_all(tmp_dir.join(".bulk")).unwrap();
+        fs::write(tmp_dir.join(".bulk/00000000000000000001.log"), b"leftover").unwrap();
+
+        // the index of the first segment can't be moved, its log already was
+        let blocker = tmp_dir.join("00000000000000000001.idx");
+        fs::create_dir_all(&blocker).unwrap();
+        let partitions = vec![vec!["first".as_bytes()], vec!["second".as_bytes()]];
+        assert!(matches!(c.bulk_load(&partitions), Err(Error::Io(_))));
+
+        assert_eq!(c.segments.len(), 1);
+        assert!(!tmp_dir.join("00000000000000000001.log").exists());
+        assert!(!tmp_dir.join(".bulk").exists());
+
+        // the next segment doesn't pick up any of the load
+        fs::remove_dir(&blocker).unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        assert_eq!(c.segments.len(), 2);
+        assert_eq!(c.segments[1].entries(), 1);
+        assert_eq!(
+            c.read_at(1, 0).unwrap(),
+            b"third-record-bigger-goes-to-another-segment"
+        );
+
+        // and a load succeeds
+        c.bulk_load(&partitions).unwrap();
+        assert_eq!(c.read_at(3, 0).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_slow_operations() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static ROTATIONS: AtomicUsize = AtomicUsize::new(0);
+        static FLUSHES: AtomicUsize = AtomicUsize::new(0);
+
+        fn hook(event: &SlowOperation) {
+            match (event.operation, event.cause) {
+                (Operation::Rotation, "segment full") => {
+                    assert_eq!(event.bytes, 32 + 25 + 21 + 32 + 2 * 16);
+                    ROTATIONS.fetch_add(1, Ordering::SeqCst);
+                }
+                (Operation::Flush, "barrier") => {
+                    FLUSHES.fetch_add(1, Ordering::SeqCst);
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::new(92, 10000);
+        // report everything
+        options.slow_operation_threshold = Some(Duration::from_secs(0));
+        options.on_slow_operation = Some(hook);
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        c.barrier().unwrap();
+
+        assert_eq!(ROTATIONS.load(Ordering::SeqCst), 1);
+        assert_eq!(FLUSHES.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_locate() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let record = Record {
+            segment_index: 0,
+            current_offset: 1,
+        };
+        c.flush_until(&record).unwrap();
+        let position = c.locate(&record).unwrap();
+        assert_eq!(
+            position,
+            FilePosition {
+                path: tmp_dir.join("00000000000000000000.log"),
+                offset: 65, // past the log header, the first record and the header of the second
+                len: 13,
+            }
+        );
+        let file = fs::read(&position.path).unwrap();
+        assert_eq!(&file[65..78], b"second-record");
+
+        let record = Record {
+            segment_index: 2,
+            current_offset: 0,
+        };
+        assert!(matches!(c.locate(&record), Err(Error::SegmentUnavailable)));
+    }
+
+    #[test]
+    fn test_bucketing() {
+        fn bucket(_: std::time::SystemTime) -> String {
+            "2024-05-10".to_string()
+        }
+
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let bucket_dir = tmp_dir.join("2024-05-10");
+        let mut options = Options::new(92, 10000);
+        options.bucketing = Bucketing::Custom(bucket);
+        let mut c = CommitLog::with_options(tmp_dir.clone(), options).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        assert!(bucket_dir.join("00000000000000000000.log").exists());
+        assert!(bucket_dir.join("00000000000000000000.ftr").exists());
+        assert!(!tmp_dir.join("00000000000000000000.log").exists());
+
+        let record = Record {
+            segment_index: 1,
+            current_offset: 0,
+        };
+        let position = c.locate(&record).unwrap();
+        assert_eq!(position.path, bucket_dir.join("00000000000000000001.log"));
+
+        // loaded segments are moved into the bucket, and sealed there later on
+        c.bulk_load(&[vec!["loaded".as_bytes()]]).unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        assert!(bucket_dir.join("00000000000000000002.ftr").exists());
+        c.barrier().unwrap();
+        drop(c);
+
+        // buckets are found regardless of the current bucketing
+        let mut c = CommitLog::new(tmp_dir.clone(), 92, 10000).unwrap();
+        assert_eq!(c.segments.len(), 4);
+        assert_eq!(c.read_at(2, 0).unwrap(), b"loaded");
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        assert!(tmp_dir.join("00000000000000000004.log").exists());
+    }
+
+    #[test]
+    fn test_expected_min_record_size() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        // 100 bytes of records after the log header
+        let mut options = Options::new(132, 0);
+        options.expected_min_record_size = Some(25);
+        assert_eq!(options.index_size(), (4 + 2) * 16); // header + 4 entries
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        // records half the expected size fill the index before the log
+        for _ in 0..5 {
+            c.write(b"twelve-bytes").unwrap();
+        }
+        assert_eq!(c.segments.len(), 2);
+        assert_eq!(c.segments[0].entries(), 4);
+
+        // the next index is sized from the observed average, 20 bytes with the record header
+        assert_eq!(c.index_size(), (100_usize.div_ceil(20) + 2) * 16);
+        for _ in 0..9 {
+            c.write(b"twelve-bytes").unwrap();
+        }
+        assert_eq!(c.segments.len(), 3);
+        assert_eq!(c.segments[1].entries(), 5);
+        assert_eq!(c.segments[1].size(), 132);
+    }
+
+    #[test]
+    fn test_barrier() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+        assert_eq!(c.last_fence().unwrap(), None);
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let fence = c.barrier().unwrap();
+        assert_eq!(fence.id, 1);
+        assert_eq!(
+            fence.head,
+            Record {
+                segment_index: 1,
+                current_offset: 1,
+            }
+        );
+        let stats = c.io_stats();
+        assert_eq!(stats.bytes_flushed, stats.bytes_written);
+
+        c.write(b"fourth-record").unwrap();
+        let fence = c.barrier().unwrap();
+        assert_eq!(fence.id, 2);
+        assert_eq!(c.last_fence().unwrap(), Some(fence));
+    }
+
+    #[test]
+    fn test_flush_dirty_bytes() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::new(1000, 10000);
+        options.flush_dirty_bytes = Some(120);
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        // 32 + 25 bytes of log + 32 + 16 of index (headers, record and entry)
+        c.write(b"this-has-less-20b").unwrap();
+        assert_eq!(c.io_stats().flushes, 0);
+
+        // 142 unflushed bytes, over the threshold
+        c.write(b"second-record").unwrap();
+        let stats = c.io_stats();
+        assert!(stats.flushes > 0);
+        assert_eq!(stats.bytes_flushed, stats.bytes_written);
+    }
+
+    #[test]
+    fn test_sampling() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::new(1000, 10000);
+        options.sample_every = Some(2);
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        for record in &["first", "second", "third", "fourth", "fifth"] {
+            c.write(record.as_bytes()).unwrap();
+        }
+
+        // records 0, 2 and 4
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while c.sampling().unwrap().checked < 3 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            c.sampling(),
+            Some(Sampling {
+                checked: 3,
+                corrupted: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::new(1000, 10000);
+        options.flush_policy = FlushPolicy::EveryRecords(2);
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        c.write(b"first").unwrap();
+        assert_eq!(c.io_stats().bytes_flushed, 0);
+        c.write(b"second").unwrap();
+        assert_eq!(c.io_stats().bytes_flushed, c.io_stats().bytes_written);
+        c.write(b"third").unwrap();
+        assert!(c.io_stats().bytes_flushed < c.io_stats().bytes_written);
+
+        c.set_flush_policy(FlushPolicy::EveryWrite);
+        c.write(b"fourth").unwrap();
+        assert_eq!(c.io_stats().bytes_flushed, c.io_stats().bytes_written);
+
+        c.set_flush_policy(FlushPolicy::Interval(Duration::from_secs(3600)));
+        c.write(b"fifth").unwrap();
+        assert!(c.io_stats().bytes_flushed < c.io_stats().bytes_written);
+        c.set_flush_policy(FlushPolicy::Interval(Duration::from_secs(0)));
+        c.write(b"sixth").unwrap();
+        assert_eq!(c.io_stats().bytes_flushed, c.io_stats().bytes_written);
+
+        c.set_flush_policy(FlushPolicy::Manual);
+        c.write(b"seventh").unwrap();
+        assert!(c.io_stats().bytes_flushed < c.io_stats().bytes_written);
+    }
+
+    #[test]
+    fn test_degraded() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut options = Options::new(92, 10000);
+        options.flush_deadline = Some(Duration::from_millis(10));
+        let mut c = CommitLog::with_options(tmp_dir, options).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        assert!(!c.is_degraded());
+
+        // simulate a flush hanging past the deadline
+        let guard = c.watchdog.as_ref().unwrap().arm();
+        std::thread::sleep(Duration::from_millis(100));
+        drop(guard);
+
+        assert!(c.is_degraded());
+        match c.write(b"second-record") {
+            Err(Error::Degraded) => {}
+            _ => panic!("expected Error::Degraded"),
+        }
+        // reads are still served
+        assert_eq!(c.read_at(0, 0).unwrap(), b"this-has-less-20b");
+    }
+
+    #[test]
+    fn test_write_validation() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 100, 1000).unwrap();
+        c.set_validation(Validation::Utf8);
+
+        assert_eq!(c.write("valid-utf8-ü".as_bytes()).unwrap(), 13);
+        match c.write(b"invalid-\xff") {
+            Err(Error::Validation(ValidationError::InvalidUtf8 { position: 8 })) => {}
+            _ => panic!("expected ValidationError::InvalidUtf8"),
+        }
+
+        // the rejected buffer was not written
+        assert_eq!(c.head().current_offset, 1);
+    }
+
+    #[test]
+    fn test_flush_until() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 112, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        let marker = c
+            .append_if_head(&c.head(), b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        c.write(b"fourth").unwrap();
+
+        let before = c.io_stats();
+        c.flush_until(&marker).unwrap();
+        let flushed = c.io_stats() - before;
+
+        // the first segment was already synced when sealed on rotation, the second one is
+        // synced (log and index) only up to the marker
+        // both headers, the record and its entry
+        assert_eq!(flushed.bytes_flushed, 32 + 51 + 32 + 16);
+        assert_eq!(flushed.flushes, 2);
+
+        assert!(c.flush_until(&Reader::next(&c.head())).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+
+        c.verify().unwrap();
+    }
+
+    #[test]
+    fn test_oversized_segment() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        CommitLog::new(tmp_dir.clone(), 132, 1000).unwrap();
+
+        // refuses to open it by default, the manifest tells it was created bigger
+        match CommitLog::new(tmp_dir.clone(), 60, 1000) {
+            Err(Error::ManifestMismatch(manifest)) => assert_eq!(manifest.segment_size, 132),
+            _ => panic!("expected an oversized segment error"),
+        }
+
+        // sealed segments are skipped on the first write
+        let mut options = Options::new(60, 1000);
+        options.oversized_segment = OversizedSegment::Seal;
+        let mut c = CommitLog::with_options(tmp_dir.clone(), options).unwrap();
+        c.write(b"first-record").unwrap();
+        assert_eq!(c.head().segment_index, 1);
+        assert_eq!(
+            fs::metadata(tmp_dir.join("00000000000000000000.log"))
+                .unwrap()
+                .len(),
+            132
+        );
+    }
+
+    #[test]
+    fn test_reopen() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 112, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        c.write(b"fourth").unwrap();
+        let head = c.head();
+        drop(c);
+
+        let mut c = CommitLog::new(tmp_dir.clone(), 112, 10000).unwrap();
+        assert_eq!(c.head(), head);
+        assert_eq!(c.read_at(0, 1).unwrap(), b"second-record");
+        assert_eq!(c.read_at(1, 1).unwrap(), b"fourth");
+
+        // writes continue after the last record, without overwriting it
+        c.write(b"5").unwrap();
+        assert_eq!(c.read_at(1, 1).unwrap(), b"fourth");
+        assert_eq!(c.read_at(1, 2).unwrap(), b"5");
+        c.verify().unwrap();
+
+        // the sealed segment is never written again
+        assert!(c.segments[0].is_sealed());
+        assert!(!c.segments[1].is_sealed());
+        drop(c);
+
+        // segments can't be skipped
+        fs::remove_file(tmp_dir.join("00000000000000000000.log")).unwrap();
+        match CommitLog::new(tmp_dir, 112, 10000) {
+            Err(Error::MissingSegment(0)) => {}
+            _ => panic!("expected Error::MissingSegment"),
+        }
+    }
+
+    #[test]
+    fn test_manifest() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 112, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger, recorded in the manifest
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        drop(c);
+
+        // other settings are refused
+        match CommitLog::new(tmp_dir.clone(), 112, 1000) {
+            Err(Error::ManifestMismatch(manifest)) => {
+                let segments = vec![(0, PathBuf::new()), (1, PathBuf::new())];
+                assert_eq!(manifest, Manifest::new(&Options::new(112, 10000), segments))
+            }
+            _ => panic!("expected Error::ManifestMismatch"),
+        }
+        let mut options = Options::new(112, 10000);
+        options.index_layout = IndexLayout::PageAligned;
+        options.oversized_segment = OversizedSegment::Adopt;
+        assert!(matches!(
+            CommitLog::with_options(tmp_dir.clone(), options),
+            Err(Error::ManifestMismatch(_))
+        ));
+
+        // the last segment can't go missing either
+        CommitLog::new(tmp_dir.clone(), 112, 10000).unwrap();
+        fs::remove_file(tmp_dir.join("00000000000000000001.log")).unwrap();
+        match CommitLog::new(tmp_dir, 112, 10000) {
+            Err(Error::MissingSegment(1)) => {}
+            _ => panic!("expected Error::MissingSegment"),
+        }
+    }
+
+    #[test]
+    fn test_manifest_segments() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let bucket = tmp_dir.join("2024-05-10");
+        let mut c = CommitLog::new(tmp_dir.clone(), 112, 10000).unwrap();
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+        drop(c);
+
+        // a segment moved to another directory
+        fs::create_dir_all(&bucket).unwrap();
+        for extension in &["log", "idx", "ftr"] {
+            let name = format!("00000000000000000000.{}", extension);
+            fs::rename(tmp_dir.join(&name), bucket.join(&name)).unwrap();
+        }
+        match CommitLog::new(tmp_dir.clone(), 112, 10000) {
+            Err(Error::MissingSegment(0)) => {}
+            _ => panic!("expected Error::MissingSegment"),
+        }
+
+        // or copied to it
+        for extension in &["log", "idx", "ftr"] {
+            let name = format!("00000000000000000000.{}", extension);
+            fs::copy(bucket.join(&name), tmp_dir.join(&name)).unwrap();
+        }
+        match CommitLog::new(tmp_dir.clone(), 112, 10000) {
+            Err(Error::DuplicateSegment(0)) => {}
+            _ => panic!("expected Error::DuplicateSegment"),
+        }
+
+        fs::remove_dir_all(&bucket).unwrap();
+        CommitLog::new(tmp_dir, 112, 10000).unwrap();
+    }
+
+    #[test]
+    fn test_max_records_per_segment() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::with_options(tmp_dir, Options::with_max_records(1000, 2)).unwrap();
+
+        c.write(b"first").unwrap();
+        c.write(b"second").unwrap();
+        // the index is full, despite the log having room
+        c.write(b"third").unwrap();
+
+        assert_eq!(c.segments.len(), 2);
+        assert_eq!(c.segments[0].entries(), 2);
+        assert_eq!(c.read_at(0, 1).unwrap(), b"second");
+        assert_eq!(c.read_at(1, 0).unwrap(), b"third");
+    }
+
+    #[cfg(feature = "latency")]
+    #[test]
+    fn test_latency_report() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+
+        let report = c.latency_report();
+        assert_eq!(report.histogram(Stage::Fit).len(), 3);
+        assert_eq!(report.histogram(Stage::IndexWrite).len(), 3);
+        assert_eq!(report.histogram(Stage::LogWrite).len(), 3);
+        assert_eq!(report.histogram(Stage::Rotation).len(), 1);
+    }
+
+    #[test]
+    fn test_read() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+
+        assert_eq!(c.read_at(0, 0).unwrap(), "this-has-less-20b".as_bytes());
+        assert_eq!(c.read_at(0, 1).unwrap(), "second-record".as_bytes());
+        assert_eq!(
+            c.read_at(1, 0).unwrap(),
+            "third-record-bigger-goes-to-another-segment".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_read_many() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+
+        assert_eq!(
+            c.read_many(0, 0, 10).unwrap(),
+            vec!["this-has-less-20b".as_bytes(), "second-record".as_bytes()]
+        );
+        assert_eq!(
+            c.read_many(1, 0, 10).unwrap(),
+            vec!["third-record-bigger-goes-to-another-segment".as_bytes()]
+        );
+        assert!(c.read_many(2, 0, 10).is_err());
+    }
+
+    #[test]
+    fn test_empty_records() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 100, 10000).unwrap();
+
+        // empty payloads are records of their own, framed by their header
+        assert_eq!(c.write(b"").unwrap(), 0);
+        c.write(b"not-empty").unwrap();
+        c.write(b"").unwrap();
+        assert_eq!(c.read_at(0, 0).unwrap(), b"");
+        assert_eq!(c.iter().count(), 3);
+        c.verify().unwrap();
+        drop(c);
+
+        // and never mistaken for unwritten index slots on reopen
+        let c = CommitLog::new(tmp_dir, 100, 10000).unwrap();
+        let records: Vec<&[u8]> = c.iter().map(|r| r.unwrap()).collect();
+        assert_eq!(records, vec![&b""[..], &b"not-empty"[..], &b""[..]]);
+        assert_eq!(c.head().current_offset, 3);
+    }
+
+    #[test]
+    fn test_iter() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 112, 10000).unwrap();
+        assert_eq!(c.iter().count(), 0);
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        // segment switch trigger
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap();
+        c.write(b"fourth").unwrap();
+
+        let records: Vec<&[u8]> = c.iter().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            records,
+            vec![
+                &b"this-has-less-20b"[..],
+                &b"second-record"[..],
+                &b"third-record-bigger-goes-to-another-segment"[..],
+                &b"fourth"[..],
+            ]
+        );
+
+        // offsets span segments
+        let records: Vec<&[u8]> = c
+            .iter_from(&Position::Offset(2))
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records[0], b"third-record-bigger-goes-to-another-segment");
+        assert_eq!(records.len(), 2);
+        assert_eq!(c.iter_from(&Position::Offset(4)).count(), 0);
+    }
 }
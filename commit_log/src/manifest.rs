@@ -0,0 +1,203 @@
+use crate::format::{
+    INDEX_VERSION, LOG_VERSION, MANIFEST_HEADER_SIZE, MANIFEST_SEGMENT_SIZE, MANIFEST_VERSION,
+};
+use crate::{IndexLayout, Options, OversizedSegment};
+
+use std::convert::TryInto;
+use std::path::PathBuf;
+
+/// Manifest
+///
+/// The configuration and state of a commit log, persisted at its root (see `Checkpoint`) so
+/// reopening it with settings it can't be read with is detected, rather than misread:
+///
+/// |---------|-------------|---------------|--------------|--------------|------------|----------|
+/// | version | log version | index version | index layout | segment size | index size | segments |
+/// |---------|-------------|---------------|--------------|--------------|------------|----------|
+///     4B          4B             4B              4B             8B            8B          8B
+///
+/// Followed by every live segment, in order:
+///
+/// |-------------|------------|-----------|
+/// | base offset | dir length | directory |
+/// |-------------|------------|-----------|
+///       8B            4B       dir length
+///
+/// All little-endian, directories are relative to the root of the log (empty for the root
+/// itself, see `Bucketing`). The manifest is rewritten every time a segment is created.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Version of the format of the log files
+    pub log_version: u32,
+    /// Version of the format of the index files
+    pub index_version: u32,
+    /// How entries are laid out on the index files
+    pub index_layout: IndexLayout,
+    /// Max size of the log files
+    pub segment_size: usize,
+    /// Max size of the index files
+    pub index_size: usize,
+    /// Live segments, their base offset and directory relative to the root of the log
+    pub segments: Vec<(usize, PathBuf)>,
+}
+
+impl Manifest {
+    /// Return the manifest of a log of the given options and segments
+    pub fn new(options: &Options, segments: Vec<(usize, PathBuf)>) -> Self {
+        Self {
+            log_version: LOG_VERSION,
+            index_version: INDEX_VERSION,
+            index_layout: options.index_layout,
+            segment_size: options.segment_size,
+            index_size: options.index_size(),
+            segments,
+        }
+    }
+
+    /// Return true if a log of this manifest can be opened with the given one's settings
+    ///
+    /// The formats and the index layout must match. Sizes may only differ when segments of
+    /// other sizes are accepted (see `OversizedSegment`), otherwise they must match too.
+    pub fn is_compatible(&self, other: &Self, policy: OversizedSegment) -> bool {
+        self.log_version == other.log_version
+            && self.index_version == other.index_version
+            && self.index_layout == other.index_layout
+            && (policy != OversizedSegment::Error
+                || (self.segment_size == other.segment_size && self.index_size == other.index_size))
+    }
+
+    /// Return the first listed segment that isn't among the `found` ones, in the same directory
+    pub fn missing(&self, found: &[(usize, PathBuf)]) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|segment| !found.contains(segment))
+            .map(|(offset, _)| *offset)
+    }
+
+    /// Encode the manifest as little-endian bytes, to be persisted
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let index_layout: u32 = match self.index_layout {
+            IndexLayout::Packed => 0,
+            IndexLayout::PageAligned => 1,
+        };
+
+        let mut bytes = Vec::with_capacity(MANIFEST_HEADER_SIZE);
+        bytes.extend_from_slice(&MANIFEST_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.log_version.to_le_bytes());
+        bytes.extend_from_slice(&self.index_version.to_le_bytes());
+        bytes.extend_from_slice(&index_layout.to_le_bytes());
+        bytes.extend_from_slice(&(self.segment_size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.index_size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.segments.len() as u64).to_le_bytes());
+        for (offset, dir) in &self.segments {
+            let dir = dir.to_string_lossy();
+            bytes.extend_from_slice(&(*offset as u64).to_le_bytes());
+            bytes.extend_from_slice(&(dir.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(dir.as_bytes());
+        }
+        bytes
+    }
+
+    /// Decode a manifest encoded by `to_bytes`, `None` if it isn't of a supported version
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < MANIFEST_HEADER_SIZE {
+            return None;
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..(at + 4)].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..(at + 8)].try_into().unwrap());
+
+        if u32_at(0) != MANIFEST_VERSION {
+            return None;
+        }
+        let index_layout = match u32_at(12) {
+            0 => IndexLayout::Packed,
+            1 => IndexLayout::PageAligned,
+            _ => return None,
+        };
+
+        let mut segments = Vec::new();
+        let mut at = MANIFEST_HEADER_SIZE;
+        for _ in 0..u64_at(32) {
+            if bytes.len() < at + MANIFEST_SEGMENT_SIZE {
+                return None;
+            }
+            let offset = u64_at(at) as usize;
+            let len = u32_at(at + 8) as usize;
+            at += MANIFEST_SEGMENT_SIZE;
+
+            let dir = std::str::from_utf8(bytes.get(at..(at + len))?).ok()?;
+            segments.push((offset, PathBuf::from(dir)));
+            at += len;
+        }
+        if at != bytes.len() {
+            return None;
+        }
+
+        Some(Self {
+            log_version: u32_at(4),
+            index_version: u32_at(8),
+            index_layout,
+            segment_size: u64_at(16) as usize,
+            index_size: u64_at(24) as usize,
+            segments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(count: usize) -> Vec<(usize, PathBuf)> {
+        (0..count).map(|offset| (offset, PathBuf::new())).collect()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut options = Options::new(100, 1000);
+        options.index_layout = IndexLayout::PageAligned;
+        let mut manifest = Manifest::new(&options, segments(2));
+        manifest.segments.push((2, PathBuf::from("2024-05-10")));
+
+        let bytes = manifest.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            MANIFEST_HEADER_SIZE + 3 * MANIFEST_SEGMENT_SIZE + 10
+        );
+        assert_eq!(Manifest::from_bytes(&bytes), Some(manifest));
+        assert_eq!(Manifest::from_bytes(&bytes[..(bytes.len() - 1)]), None);
+        assert_eq!(Manifest::from_bytes(&[0; 8]), None);
+        assert_eq!(Manifest::from_bytes(&[0; MANIFEST_HEADER_SIZE]), None);
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let manifest = Manifest::new(&Options::new(100, 1000), segments(3));
+
+        // the amount of segments changes as the log grows
+        let grown = Manifest::new(&Options::new(100, 1000), segments(4));
+        assert!(manifest.is_compatible(&grown, OversizedSegment::Error));
+
+        let resized = Manifest::new(&Options::new(200, 1000), segments(3));
+        assert!(!manifest.is_compatible(&resized, OversizedSegment::Error));
+        assert!(manifest.is_compatible(&resized, OversizedSegment::Adopt));
+
+        let mut options = Options::new(100, 1000);
+        options.index_layout = IndexLayout::PageAligned;
+        let realigned = Manifest::new(&options, segments(3));
+        assert!(!manifest.is_compatible(&realigned, OversizedSegment::Adopt));
+    }
+
+    #[test]
+    fn test_missing() {
+        let manifest = Manifest::new(&Options::new(100, 1000), segments(3));
+        assert_eq!(manifest.missing(&segments(3)), None);
+        // segments created after the manifest was stored, e.g. on a crash
+        assert_eq!(manifest.missing(&segments(4)), None);
+
+        let mut moved = segments(3);
+        moved[1].1 = PathBuf::from("2024-05-10");
+        assert_eq!(manifest.missing(&moved), Some(1));
+        assert_eq!(manifest.missing(&segments(2)), Some(2));
+    }
+}
@@ -1,7 +1,11 @@
-use crate::{CommitLog, Position, Record};
+use crate::format::RECORD_HEADER_SIZE;
+use crate::{Annotation, CommitLog, Position, Record};
 
+use std::cell::Cell;
 use std::io;
 use std::result::Result;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use derive_more::From;
 
@@ -12,27 +16,348 @@ pub enum Error {
     InvalidPosition,
 }
 
+/// Isolation
+///
+/// Which segments a reader sees.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Isolation {
+    /// Every segment, including the active one being written to.
+    #[default]
+    Active,
+    /// Sealed segments only, trading freshness for never racing with the writer. Every
+    /// segment of a log opened read-only counts as sealed, see `CommitLog::open_read_only`.
+    Sealed,
+}
+
+/// Reader
+///
+/// Built with `Reader::new` or `Reader::with_isolation`, fields may be added over time.
+#[non_exhaustive]
 pub struct Reader<'a> {
     pub commit_log: &'a CommitLog,
+    pub isolation: Isolation,
+
+    /// Limits the reads of sealed segments, `None` when unthrottled
+    throttle: Option<Throttle>,
+}
+
+/// Throttle
+///
+/// Paces reads to a given amount of bytes per second, sleeping when they get ahead.
+struct Throttle {
+    bytes_per_sec: u64,
+
+    /// When the first throttled read happened
+    started: Cell<Option<Instant>>,
+
+    /// Amount of bytes read since `started`
+    bytes: Cell<u64>,
+
+    /// Total time spent sleeping
+    throttled: Cell<Duration>,
+}
+
+/// Progress
+///
+/// The outcome of a budgeted read, the records read so far and where to resume from.
+pub struct Progress<'a> {
+    /// Records read, in order.
+    pub records: Vec<&'a [u8]>,
+    /// Total amount of bytes read.
+    pub bytes: usize,
+    /// The record to resume reading from.
+    pub next: Record,
+}
+
+/// Fetch
+///
+/// A contiguous range of records of a segment, as laid out in its log file, see
+/// `Reader::fetch`.
+pub struct Fetch<'a> {
+    /// The records, each one prefixed by its header (CRC32C and length)
+    pub bytes: &'a [u8],
+    /// The size of each record in `bytes`, header included, in order.
+    pub sizes: Vec<usize>,
+    /// The record to resume fetching from.
+    pub next: Record,
+}
+
+/// Batch
+///
+/// Records copied out of the log, see `Reader::poll_batch`. Unlike `Progress` and `Fetch`
+/// it doesn't borrow the log, so it can be handed over to other threads or frameworks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    /// Records read, in order.
+    pub records: Vec<Vec<u8>>,
+    /// Total amount of bytes read (payloads only).
+    pub bytes: usize,
+    /// The record to resume polling from.
+    pub next: Record,
+}
+
+impl<'a> Fetch<'a> {
+    /// Return the payload of each record fetched, in order
+    pub fn records(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        let bytes = self.bytes;
+        self.sizes.iter().scan(0, move |start, size| {
+            let record = &bytes[(*start + RECORD_HEADER_SIZE)..(*start + size)];
+            *start += size;
+            Some(record)
+        })
+    }
 }
 
 impl<'a> Reader<'a> {
+    /// Return a reader over every segment of the log
+    pub fn new(commit_log: &'a CommitLog) -> Self {
+        Self::with_isolation(commit_log, Isolation::default())
+    }
+
+    /// Return a reader only seeing the segments allowed by the isolation
+    pub fn with_isolation(commit_log: &'a CommitLog, isolation: Isolation) -> Self {
+        Self {
+            commit_log,
+            isolation,
+            throttle: None,
+        }
+    }
+
+    /// Limit reads of sealed segments to `bytes_per_sec`, so scanning old data doesn't evict
+    /// the pages of the active segment too fast. Reads of the active segment are never throttled.
+    ///
+    /// A rate of zero removes the throttle.
+    pub fn set_throttle(&mut self, bytes_per_sec: u64) {
+        if bytes_per_sec == 0 {
+            self.throttle = None;
+            return;
+        }
+
+        self.throttle = Some(Throttle {
+            bytes_per_sec,
+            started: Cell::new(None),
+            bytes: Cell::new(0),
+            throttled: Cell::new(Duration::from_secs(0)),
+        });
+    }
+
+    /// Total time spent waiting on the throttle
+    pub fn throttled(&self) -> Duration {
+        self.throttle
+            .as_ref()
+            .map_or(Duration::from_secs(0), |t| t.throttled.get())
+    }
+
     /// Read the log according to record's information.
     ///
     /// # Arguments
     /// * `record` - A Record to be read.
-    pub fn read(&self, record: &Record) -> Result<&[u8], Error> {
+    pub fn read(&self, record: &Record) -> Result<&'a [u8], Error> {
         let segment_index = record.segment_index;
-        let total_segments = self.commit_log.segments.len();
-        if segment_index >= total_segments {
+        if segment_index >= self.visible_segments() {
             Err(Error::InvalidPosition)
         } else {
             let segment = &self.commit_log.segments[segment_index];
             let buf = segment.read_at(record.current_offset)?;
+
+            if let Some(throttle) = &self.throttle {
+                if segment_index + 1 < self.commit_log.segments.len() {
+                    throttle.consume(buf.len());
+                }
+            }
+
             Ok(buf)
         }
     }
 
+    /// Read the record, or `None` if it isn't available yet
+    ///
+    /// A record isn't available yet when it's past the last record of the last visible
+    /// segment, or in a segment that doesn't exist yet. Meant for event loops polling for new
+    /// records, where that is the expected outcome rather than an error.
+    ///
+    /// # Arguments
+    /// * `record` - A Record to be read.
+    pub fn try_read(&self, record: &Record) -> Result<Option<&'a [u8]>, Error> {
+        let visible = self.visible_segments();
+        if record.segment_index >= visible {
+            return Ok(None);
+        }
+
+        let last = record.segment_index + 1 == visible;
+        if last && record.current_offset >= self.commit_log.segments[visible - 1].entries() {
+            return Ok(None);
+        }
+
+        self.read(record).map(Some)
+    }
+
+    /// Return the amount of segments allowed by the isolation
+    fn visible_segments(&self) -> usize {
+        match self.isolation {
+            Isolation::Active => self.commit_log.segments.len(),
+            // nothing writes to the last segment of a read-only log
+            Isolation::Sealed if self.commit_log.is_read_only() => self.commit_log.segments.len(),
+            // the active segment is always the last one
+            Isolation::Sealed => self.commit_log.segments.len() - 1,
+        }
+    }
+
+    /// Return the annotations covering the record, in the order they were added
+    pub fn annotations(&self, record: &Record) -> Vec<&'a Annotation> {
+        self.commit_log.annotations.covering(record)
+    }
+
+    /// Read records starting at `record`, until the log ends, `max_bytes` are read or the
+    /// `deadline` passes, whichever comes first.
+    ///
+    /// At least one record is read (if available), so repeated calls always make progress.
+    /// Records that can't be read (e.g. corrupted) fail the call, they are never skipped.
+    ///
+    /// # Arguments
+    /// * `record` - The first record to be read.
+    /// * `max_bytes` - The byte budget, reading stops once it is reached.
+    /// * `deadline` - The time budget, reading stops once it passes.
+    pub fn read_budgeted(
+        &self,
+        record: &Record,
+        max_bytes: usize,
+        deadline: Instant,
+    ) -> Result<Progress<'a>, Error> {
+        let mut progress = Progress {
+            records: Vec::new(),
+            bytes: 0,
+            next: Reader::record_after(record, 0),
+        };
+
+        while progress.bytes < max_bytes
+            && (progress.records.is_empty() || Instant::now() < deadline)
+        {
+            let visible = &self.commit_log.segments[..self.visible_segments()];
+            let segment = match visible.get(progress.next.segment_index) {
+                Some(segment) => segment,
+                None => break,
+            };
+            if progress.next.current_offset >= segment.entries() {
+                // the current segment is over, move on to the next one once it has records
+                let next_segment = Reader::next_segment(&progress.next);
+                match visible.get(next_segment.segment_index) {
+                    Some(segment) if segment.entries() > 0 => {
+                        progress.next = next_segment;
+                        continue;
+                    }
+                    _ => break,
+                }
+            }
+
+            let buf = self.read(&progress.next)?;
+
+            progress.bytes += buf.len();
+            progress.records.push(buf);
+            progress.next = Reader::next(&progress.next);
+        }
+
+        Ok(progress)
+    }
+
+    /// Fetch the records starting at `record` as a single contiguous slice, until the next one
+    /// would go past `max_bytes` or the segment ends.
+    ///
+    /// At least one record is fetched (if available), so repeated calls always make progress.
+    /// Fetching past the end of a segment moves on to the next one, an empty fetch means the
+    /// end of the log was reached.
+    ///
+    /// # Arguments
+    /// * `record` - The first record to be fetched.
+    /// * `max_bytes` - The byte budget, headers included.
+    pub fn fetch(&self, record: &Record, max_bytes: usize) -> Result<Fetch<'a>, Error> {
+        let visible = self.visible_segments();
+        let segments = &self.commit_log.segments;
+
+        let mut next = Reader::record_after(record, 0);
+        while next.segment_index + 1 < visible
+            && next.current_offset >= segments[next.segment_index].entries()
+        {
+            next = Reader::next_segment(&next);
+        }
+        if next.segment_index >= visible {
+            return Ok(Fetch {
+                bytes: &[],
+                sizes: Vec::new(),
+                next,
+            });
+        }
+
+        let (bytes, sizes) = segments[next.segment_index].fetch(next.current_offset, max_bytes)?;
+        if let Some(throttle) = &self.throttle {
+            if next.segment_index + 1 < segments.len() {
+                throttle.consume(bytes.len());
+            }
+        }
+
+        Ok(Fetch {
+            bytes,
+            next: Reader::record_after(&next, sizes.len()),
+            sizes,
+        })
+    }
+
+    /// Poll a batch of records starting at `record`, copied out of the log
+    ///
+    /// Reading stops once `max_records` are read, `max_bytes` of payload are reached, the log
+    /// ends or `time_budget` elapses, whichever comes first. At least one record is read (if
+    /// available and `max_records` isn't zero), so repeated calls always make progress, even
+    /// with a `max_bytes` of zero. A budget too big to be represented (e.g. `Duration::MAX`)
+    /// never elapses.
+    ///
+    /// It never waits for records to be written: when none are available past `record`, it
+    /// returns an empty batch right away. Callers tailing the log must back off between polls
+    /// rather than calling it in a tight loop.
+    ///
+    /// # Arguments
+    /// * `record` - The first record to be read.
+    /// * `max_records` - The amount of records to read at most.
+    /// * `max_bytes` - The byte budget, reading stops once it is reached.
+    /// * `time_budget` - The time budget, reading stops once it elapses.
+    pub fn poll_batch(
+        &self,
+        record: &Record,
+        max_records: usize,
+        max_bytes: usize,
+        time_budget: Duration,
+    ) -> Result<Batch, Error> {
+        let deadline = Instant::now().checked_add(time_budget);
+        let mut batch = Batch {
+            records: Vec::new(),
+            bytes: 0,
+            next: Reader::record_after(record, 0),
+        };
+
+        while batch.records.len() < max_records
+            && (batch.records.is_empty()
+                || (batch.bytes < max_bytes
+                    && deadline.is_none_or(|deadline| Instant::now() < deadline)))
+        {
+            let fetch = self.fetch(&batch.next, max_bytes.saturating_sub(batch.bytes))?;
+            let taken = fetch.sizes.len().min(max_records - batch.records.len());
+            for buf in fetch.records().take(taken) {
+                batch.bytes += buf.len();
+                batch.records.push(buf.to_vec());
+            }
+
+            // the records fetched past `max_records` are left for the next poll
+            batch.next = Record {
+                segment_index: fetch.next.segment_index,
+                current_offset: fetch.next.current_offset - (fetch.sizes.len() - taken),
+            };
+            if taken == 0 {
+                break;
+            }
+        }
+
+        Ok(batch)
+    }
+
     /// Read the position of one record
     ///
     /// # Arguments
@@ -66,16 +391,34 @@ impl<'a> Reader<'a> {
     }
 }
 
+impl Throttle {
+    /// Account for `bytes` read, sleeping until they are within the rate
+    fn consume(&self, bytes: usize) {
+        let started = self.started.get().unwrap_or_else(Instant::now);
+        self.started.set(Some(started));
+        self.bytes.set(self.bytes.get() + bytes as u64);
+
+        let expected = Duration::from_secs_f64(self.bytes.get() as f64 / self.bytes_per_sec as f64);
+        let elapsed = started.elapsed();
+        if expected > elapsed {
+            thread::sleep(expected - elapsed);
+            self.throttled
+                .set(self.throttled.get() + (expected - elapsed));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate tempfile;
     use super::*;
+    use std::time::Duration;
     use tempfile::tempdir;
 
     #[test]
     fn test_read() {
         let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 50, 10000).unwrap();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
 
         c.write(b"this-has-less-20b").unwrap();
         c.write(b"second-record").unwrap();
@@ -86,17 +429,345 @@ mod tests {
             current_offset: 0,
             segment_index: 0,
         };
-        let reader = Reader { commit_log: &c };
+        let reader = Reader::new(&c);
         assert_eq!(
             reader.read(&record).unwrap(),
             "this-has-less-20b".as_bytes()
         );
     }
 
+    #[test]
+    fn test_try_read() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let record = |segment_index, current_offset| Record {
+            segment_index,
+            current_offset,
+        };
+        let reader = Reader::new(&c);
+        assert_eq!(
+            reader.try_read(&record(0, 1)).unwrap(),
+            Some(&b"second-record"[..])
+        );
+        assert_eq!(
+            reader.try_read(&record(1, 0)).unwrap(),
+            Some(&b"third-record-bigger-goes-to-another-segment"[..])
+        );
+
+        // not written yet
+        assert_eq!(reader.try_read(&record(1, 1)).unwrap(), None);
+        assert_eq!(reader.try_read(&record(2, 0)).unwrap(), None);
+
+        // never going to be written, the segment is sealed
+        assert!(reader.try_read(&record(0, 2)).is_err());
+
+        // the active segment isn't visible yet
+        let reader = Reader::with_isolation(&c, Isolation::Sealed);
+        assert_eq!(reader.try_read(&record(1, 0)).unwrap(), None);
+        assert_eq!(reader.try_read(&record(0, 2)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_budgeted() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let reader = Reader::new(&c);
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        // stops once the byte budget is reached
+        let progress = reader.read_budgeted(&record, 20, deadline).unwrap();
+        assert_eq!(
+            progress.records,
+            vec!["this-has-less-20b".as_bytes(), "second-record".as_bytes()]
+        );
+        assert_eq!(progress.bytes, 30);
+        assert_eq!(progress.next.segment_index, 0);
+        assert_eq!(progress.next.current_offset, 2);
+
+        // crosses segments and stops at the end of the log
+        let progress = reader
+            .read_budgeted(&progress.next, 1000, deadline)
+            .unwrap();
+        assert_eq!(
+            progress.records,
+            vec!["third-record-bigger-goes-to-another-segment".as_bytes()]
+        );
+        assert_eq!(progress.next.segment_index, 1);
+        assert_eq!(progress.next.current_offset, 1);
+
+        // nothing left to read
+        let progress = reader
+            .read_budgeted(&progress.next, 1000, deadline)
+            .unwrap();
+        assert!(progress.records.is_empty());
+        assert_eq!(progress.next.segment_index, 1);
+        assert_eq!(progress.next.current_offset, 1);
+
+        // an expired deadline still reads one record
+        let progress = reader.read_budgeted(&record, 1000, Instant::now()).unwrap();
+        assert_eq!(progress.records, vec!["this-has-less-20b".as_bytes()]);
+    }
+
+    #[test]
+    fn test_read_budgeted_corrupted() {
+        use std::fs;
+        use std::io::{Seek, SeekFrom, Write};
+
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir.clone(), 132, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record").unwrap();
+
+        // a flipped bit in the payload of the second record
+        let mut log = fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_dir.join("00000000000000000000.log"))
+            .unwrap();
+        log.seek(SeekFrom::Start(66)).unwrap();
+        log.write_all(b"X").unwrap();
+
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        // the progress doesn't borrow the reader
+        let progress = Reader::new(&c)
+            .read_budgeted(&record, 10, deadline)
+            .unwrap();
+        assert_eq!(progress.records, vec!["this-has-less-20b".as_bytes()]);
+
+        // the corrupted record isn't skipped, along with the rest of the segment
+        assert!(matches!(
+            Reader::new(&c).read_budgeted(&progress.next, 1000, deadline),
+            Err(Error::Segment(_))
+        ));
+    }
+
+    #[test]
+    fn test_fetch() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let reader = Reader::new(&c);
+
+        // a single slice of both records of the first segment
+        let fetch = reader.fetch(&record, 1000).unwrap();
+        assert_eq!(fetch.bytes.len(), 25 + 21);
+        assert_eq!(fetch.sizes, vec![25, 21]);
+        assert_eq!(
+            fetch.records().collect::<Vec<_>>(),
+            vec!["this-has-less-20b".as_bytes(), "second-record".as_bytes()]
+        );
+        assert_eq!(fetch.next, Reader::record_after(&record, 2));
+
+        // moves on to the next segment
+        let fetch = reader.fetch(&fetch.next, 1000).unwrap();
+        assert_eq!(
+            fetch.records().collect::<Vec<_>>(),
+            vec!["third-record-bigger-goes-to-another-segment".as_bytes()]
+        );
+        assert_eq!(fetch.next.segment_index, 1);
+        assert_eq!(fetch.next.current_offset, 1);
+
+        // nothing left to fetch
+        let fetch = reader.fetch(&fetch.next, 1000).unwrap();
+        assert!(fetch.bytes.is_empty());
+        assert_eq!(fetch.next.current_offset, 1);
+
+        // stops before the budget is exceeded, but always fetches one record
+        let fetch = reader.fetch(&record, 40).unwrap();
+        assert_eq!(fetch.sizes, vec![25]);
+        let fetch = reader.fetch(&record, 0).unwrap();
+        assert_eq!(fetch.sizes, vec![25]);
+    }
+
+    #[test]
+    fn test_poll_batch() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let reader = Reader::new(&c);
+        let budget = Duration::from_secs(60);
+
+        // stops at the amount of records
+        let batch = reader.poll_batch(&record, 1, 1000, budget).unwrap();
+        assert_eq!(batch.records, vec![b"this-has-less-20b".to_vec()]);
+        assert_eq!(batch.next, Reader::record_after(&record, 1));
+
+        // crosses segments and stops at the end of the log
+        let batch = reader.poll_batch(&batch.next, 10, 1000, budget).unwrap();
+        assert_eq!(
+            batch.records,
+            vec![
+                b"second-record".to_vec(),
+                b"third-record-bigger-goes-to-another-segment".to_vec()
+            ]
+        );
+        assert_eq!(batch.bytes, 13 + 43);
+        assert_eq!(batch.next.segment_index, 1);
+        assert_eq!(batch.next.current_offset, 1);
+
+        // nothing left to read, returns right away rather than waiting out the budget
+        let next = batch.next;
+        let batch = reader.poll_batch(&next, 10, 1000, budget).unwrap();
+        assert!(batch.records.is_empty());
+        assert_eq!(batch.next, next);
+
+        // stops at the byte budget, but always reads one record
+        let batch = reader.poll_batch(&record, 10, 1, budget).unwrap();
+        assert_eq!(batch.records.len(), 1);
+        let batch = reader.poll_batch(&record, 10, 0, budget).unwrap();
+        assert_eq!(batch.records, vec![b"this-has-less-20b".to_vec()]);
+
+        // a budget that never elapses
+        let batch = reader.poll_batch(&record, 10, 1000, Duration::MAX).unwrap();
+        assert_eq!(batch.records.len(), 3);
+
+        // an elapsed budget stops after the first segment
+        let batch = reader
+            .poll_batch(&record, 10, 1000, Duration::from_secs(0))
+            .unwrap();
+        assert_eq!(batch.records.len(), 2);
+    }
+
+    #[test]
+    fn test_sealed_isolation() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let reader = Reader::with_isolation(&c, Isolation::Sealed);
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        // the active segment is invisible
+        let progress = reader.read_budgeted(&record, 1000, deadline).unwrap();
+        assert_eq!(
+            progress.records,
+            vec!["this-has-less-20b".as_bytes(), "second-record".as_bytes()]
+        );
+        assert!(matches!(
+            reader.read(&Reader::next_segment(&record)),
+            Err(Error::InvalidPosition)
+        ));
+
+        // nothing is active in a log opened read-only
+        let r = CommitLog::open_read_only(c.path.clone()).unwrap();
+        let reader = Reader::with_isolation(&r, Isolation::Sealed);
+        assert_eq!(
+            reader.read(&Reader::next_segment(&record)).unwrap(),
+            b"third-record-bigger-goes-to-another-segment"
+        );
+    }
+
+    #[test]
+    fn test_throttle() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+        c.write(b"third-record-bigger-goes-to-another-segment")
+            .unwrap(); // segment switch trigger
+
+        let mut reader = Reader::new(&c);
+        reader.set_throttle(1000);
+
+        // the active segment is not throttled
+        reader
+            .read(&Record {
+                current_offset: 0,
+                segment_index: 1,
+            })
+            .unwrap();
+        assert_eq!(reader.throttled(), Duration::from_secs(0));
+
+        // 30 bytes at 1000 bytes/sec take about 30ms
+        let record = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let start = Instant::now();
+        reader.read(&record).unwrap();
+        reader.read(&Reader::next(&record)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(25));
+        assert!(reader.throttled() > Duration::from_secs(0));
+
+        // a zero rate is unthrottled
+        reader.set_throttle(0);
+        reader.read(&record).unwrap();
+        assert_eq!(reader.throttled(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
+
+        c.write(b"this-has-less-20b").unwrap();
+        c.write(b"second-record").unwrap();
+
+        let first = Record {
+            current_offset: 0,
+            segment_index: 0,
+        };
+        let second = Reader::next(&first);
+        c.annotate(&second, &second, b"quarantined").unwrap();
+
+        let reader = Reader::new(&c);
+        assert!(reader.annotations(&first).is_empty());
+        assert_eq!(reader.annotations(&second)[0].data, b"quarantined");
+    }
+
     #[test]
     fn test_record_after() {
         let tmp_dir = tempdir().unwrap().path().to_owned();
-        let mut c = CommitLog::new(tmp_dir, 50, 10000).unwrap();
+        let mut c = CommitLog::new(tmp_dir, 92, 10000).unwrap();
 
         c.write(b"this-has-less-20b").unwrap();
         c.write(b"second-record").unwrap();
@@ -107,7 +778,7 @@ mod tests {
             current_offset: 0,
             segment_index: 0,
         };
-        let reader = Reader { commit_log: &c };
+        let reader = Reader::new(&c);
         let record_after = Reader::record_after(&record, 1);
         assert_eq!(record_after.current_offset, 1);
         assert_eq!(record_after.segment_index, 0);
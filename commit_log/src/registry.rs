@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Directories of the commit logs open in this process
+static OPEN: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// Registration
+///
+/// Marks a directory as owned by a commit log of this process, until dropped.
+///
+/// Two commit logs writing to the same directory corrupt each other's state, so registering a
+/// directory that is already registered fails. Paths are canonicalized, so different spellings
+/// of the same directory are caught too.
+#[derive(Debug)]
+pub struct Registration {
+    path: PathBuf,
+}
+
+impl Registration {
+    /// Register the (existing) directory, `None` if it's already registered
+    pub fn register(path: &Path) -> std::io::Result<Option<Self>> {
+        let path = path.canonicalize()?;
+
+        let mut open = OPEN.lock().unwrap();
+        if !open.get_or_insert_with(HashSet::new).insert(path.clone()) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { path }))
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(open) = OPEN.lock().unwrap().as_mut() {
+            open.remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_register() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().to_path_buf();
+
+        let registration = Registration::register(&path).unwrap();
+        assert!(registration.is_some());
+        // the same directory, spelled differently
+        assert!(Registration::register(&path.join(".")).unwrap().is_none());
+
+        drop(registration);
+        assert!(Registration::register(&path).unwrap().is_some());
+    }
+}
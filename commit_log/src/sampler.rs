@@ -0,0 +1,174 @@
+use crate::format::RECORD_HEADER_SIZE;
+use crate::segment::is_intact;
+use crate::{FilePosition, Record};
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Sampler
+///
+/// A background thread re-reading a sample of the records written straight from their log
+/// files, and checking them against their CRC. Silent corruption (e.g. bad RAM or disk) is
+/// then caught shortly after the write, instead of when consumers reach the record.
+///
+/// Corrupted records are reported as errors through the `log` crate, and counted along with
+/// the records checked, see `Sampling`.
+#[derive(Debug)]
+pub struct Sampler {
+    /// Hands samples over to the thread, dropped first to stop it
+    sender: Option<Sender<(Record, FilePosition)>>,
+    stats: Arc<Stats>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    checked: AtomicUsize,
+    corrupted: AtomicUsize,
+}
+
+/// Sampling
+///
+/// Counters of the records re-read by the sampler, see `Options::sample_every`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Records re-read and checked so far
+    pub checked: usize,
+    /// Records that didn't match their CRC
+    pub corrupted: usize,
+}
+
+impl Sampler {
+    /// Spawn the sampler thread
+    pub fn spawn() -> io::Result<Self> {
+        let stats = Arc::new(Stats::default());
+        let (sender, receiver) = mpsc::channel();
+
+        let counted = Arc::clone(&stats);
+        let thread = thread::Builder::new()
+            .name("voik-sampler".into())
+            .spawn(move || check(receiver, &counted))?;
+
+        Ok(Self {
+            sender: Some(sender),
+            stats,
+            thread: Some(thread),
+        })
+    }
+
+    /// Queue the record to be re-read, `position` being where its payload lives on disk
+    pub fn sample(&self, record: Record, position: FilePosition) {
+        if let Some(sender) = &self.sender {
+            // the thread only stops once the sender is dropped
+            let _ = sender.send((record, position));
+        }
+    }
+
+    /// Return the counters of the records checked so far
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            checked: self.stats.checked.load(Ordering::Acquire),
+            corrupted: self.stats.corrupted.load(Ordering::Acquire),
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn check(receiver: Receiver<(Record, FilePosition)>, stats: &Stats) {
+    for (record, position) in receiver {
+        let intact = match read(&position) {
+            Ok(buf) => is_intact(&buf),
+            Err(e) => {
+                log::warn!("could not re-read sampled record {:?}: {}", record, e);
+                continue;
+            }
+        };
+
+        if !intact {
+            log::error!(
+                "sampled record {:?} doesn't match its CRC, in {:?}",
+                record,
+                position.path
+            );
+            stats.corrupted.fetch_add(1, Ordering::AcqRel);
+        }
+        stats.checked.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Read the whole record (header included) from the file
+fn read(position: &FilePosition) -> io::Result<Vec<u8>> {
+    let mut file = File::open(&position.path)?;
+    file.seek(SeekFrom::Start(
+        (position.offset - RECORD_HEADER_SIZE) as u64,
+    ))?;
+
+    let mut buf = vec![0; RECORD_HEADER_SIZE + position.len];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use crate::CommitLog;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_corrupted_record() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        let mut c = CommitLog::new(tmp_dir, 100, 1000).unwrap();
+        c.write(b"first-record").unwrap();
+        c.write(b"second-record").unwrap();
+
+        let records: Vec<Record> = (0..2)
+            .map(|offset| Record {
+                segment_index: 0,
+                current_offset: offset,
+            })
+            .collect();
+        let positions: Vec<FilePosition> = records.iter().map(|r| c.locate(r).unwrap()).collect();
+
+        // a flipped byte in the payload of the second record
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&positions[1].path)
+            .unwrap();
+        file.seek(SeekFrom::Start(positions[1].offset as u64))
+            .unwrap();
+        file.write_all(b"X").unwrap();
+
+        let sampler = Sampler::spawn().unwrap();
+        for (record, position) in records.into_iter().zip(positions) {
+            sampler.sample(record, position);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while sampler.sampling().checked < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            sampler.sampling(),
+            Sampling {
+                checked: 2,
+                corrupted: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::format::{
+    CRC_SIZE, FOOTER_CRC_AT, FOOTER_ENTRIES_AT, FOOTER_INDEX_CRC_AT, FOOTER_LOG_SIZE_AT,
+    FOOTER_MAGIC as MAGIC, FOOTER_SIZE, MAGIC_SIZE,
+};
+use crc::crc32;
+
+/// Footer
+///
+/// Summary of a sealed segment, written next to its files when it's sealed:
+///
+/// 00000000000011812312.log
+/// 00000000000011812312.idx
+/// 00000000000011812312.ftr
+///
+/// A segment with a valid footer can be opened without scanning its index nor verifying its
+/// log, a missing or invalid footer means the segment wasn't sealed cleanly (e.g. a crash),
+/// and its tail must be re-validated.
+///
+/// The footer is laid out as:
+///
+/// |-------|---------|------|-----------|-------|
+/// | magic | entries | size | index crc | crc32 |
+/// |-------|---------|------|-----------|-------|
+///   4B       8B       8B       4B         4B
+///
+/// Where `size` is the amount of bytes written to the log, `index crc` the CRC32C of the
+/// index entries, and the trailing CRC32C covers everything before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footer {
+    /// Amount of records of the segment
+    pub entries: usize,
+    /// Amount of bytes written to the log
+    pub size: usize,
+    /// CRC32C of the index entries
+    pub index_crc: u32,
+}
+
+impl Footer {
+    /// Durably write the footer to the given path
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut buffer = Vec::with_capacity(FOOTER_SIZE);
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&(self.entries as u64).to_le_bytes());
+        buffer.extend_from_slice(&(self.size as u64).to_le_bytes());
+        buffer.extend_from_slice(&self.index_crc.to_le_bytes());
+        let crc = crc32::checksum_castagnoli(&buffer);
+        buffer.extend_from_slice(&crc.to_le_bytes());
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.write_all(&buffer)?;
+        file.sync_all()
+    }
+
+    /// Read the footer at the given path, `None` if it's missing or invalid
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let buffer = match fs::read(path) {
+            Ok(buffer) => buffer,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        if buffer.len() != FOOTER_SIZE || &buffer[0..MAGIC_SIZE] != MAGIC {
+            return Ok(None);
+        }
+
+        let mut crc = [0; CRC_SIZE];
+        crc.copy_from_slice(&buffer[FOOTER_CRC_AT..FOOTER_SIZE]);
+        if crc32::checksum_castagnoli(&buffer[0..FOOTER_CRC_AT]) != u32::from_le_bytes(crc) {
+            return Ok(None);
+        }
+
+        let mut entries = [0; 8];
+        entries.copy_from_slice(&buffer[FOOTER_ENTRIES_AT..FOOTER_LOG_SIZE_AT]);
+        let mut size = [0; 8];
+        size.copy_from_slice(&buffer[FOOTER_LOG_SIZE_AT..FOOTER_INDEX_CRC_AT]);
+        let mut index_crc = [0; CRC_SIZE];
+        index_crc.copy_from_slice(&buffer[FOOTER_INDEX_CRC_AT..FOOTER_CRC_AT]);
+
+        Ok(Some(Self {
+            entries: u64::from_le_bytes(entries) as usize,
+            size: u64::from_le_bytes(size) as usize,
+            index_crc: u32::from_le_bytes(index_crc),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_and_read() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("00000000000000000000.ftr");
+        assert_eq!(Footer::read(&path).unwrap(), None);
+
+        let footer = Footer {
+            entries: 2,
+            size: 27,
+            index_crc: 42,
+        };
+        footer.write(&path).unwrap();
+        assert_eq!(Footer::read(&path).unwrap(), Some(footer));
+
+        // a damaged footer is ignored
+        let mut buffer = fs::read(&path).unwrap();
+        buffer[4] ^= 0xff;
+        fs::write(&path, buffer).unwrap();
+        assert_eq!(Footer::read(&path).unwrap(), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::format::{MAGIC_SIZE, SEGMENT_HEADER_SIZE};
+
+use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The file doesn't start with the header of a supported format
+    UnsupportedFormat,
+    /// The file belongs to a segment of another base offset
+    BaseOffsetMismatch,
+}
+
+/// Header
+///
+/// The first bytes of both the log and the index files, identifying the file, the version of
+/// its format and the segment it belongs to:
+///
+/// |-------|---------|------------|-------------|----------|
+/// | magic | version | created at | base offset | reserved |
+/// |-------|---------|------------|-------------|----------|
+///   4B        4B          8B            8B          8B
+///
+/// All little-endian, `created at` in milliseconds since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub version: u32,
+    pub created_at: u64,
+    pub base_offset: u64,
+}
+
+impl Header {
+    /// Return the header of a file created now
+    pub fn new(version: u32, base_offset: usize) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+
+        Self {
+            version,
+            created_at,
+            base_offset: base_offset as u64,
+        }
+    }
+
+    /// Encode the header, to be written at the beginning of the file
+    pub fn encode(&self, magic: &[u8; MAGIC_SIZE]) -> [u8; SEGMENT_HEADER_SIZE] {
+        let mut header = [0; SEGMENT_HEADER_SIZE];
+        header[0..4].copy_from_slice(magic);
+        header[4..8].copy_from_slice(&self.version.to_le_bytes());
+        header[8..16].copy_from_slice(&self.created_at.to_le_bytes());
+        header[16..24].copy_from_slice(&self.base_offset.to_le_bytes());
+        header
+    }
+
+    /// Decode the header at the beginning of the buffer
+    ///
+    /// Returns `None` if there is no header yet (a new file), the header must be of the given
+    /// magic and version, and of the given base offset when set.
+    pub fn decode(
+        buffer: &[u8],
+        magic: &[u8; MAGIC_SIZE],
+        version: u32,
+        base_offset: Option<usize>,
+    ) -> Result<Option<Self>, Error> {
+        if buffer.len() < SEGMENT_HEADER_SIZE
+            || buffer[0..SEGMENT_HEADER_SIZE].iter().all(|b| *b == 0)
+        {
+            return Ok(None);
+        }
+        if &buffer[0..MAGIC_SIZE] != magic {
+            return Err(Error::UnsupportedFormat);
+        }
+
+        let header = Self {
+            version: u32::from_le_bytes(buffer[4..8].try_into().unwrap()),
+            created_at: u64::from_le_bytes(buffer[8..16].try_into().unwrap()),
+            base_offset: u64::from_le_bytes(buffer[16..24].try_into().unwrap()),
+        };
+        if header.version != version {
+            return Err(Error::UnsupportedFormat);
+        }
+        if base_offset.is_some_and(|base_offset| header.base_offset != base_offset as u64) {
+            return Err(Error::BaseOffsetMismatch);
+        }
+
+        Ok(Some(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let header = Header::new(1, 42);
+        let buffer = header.encode(b"TEST");
+
+        assert_eq!(
+            Header::decode(&buffer, b"TEST", 1, Some(42)),
+            Ok(Some(header))
+        );
+        assert_eq!(Header::decode(&buffer, b"TEST", 1, None), Ok(Some(header)));
+        assert_eq!(
+            Header::decode(&buffer, b"TEST", 1, Some(7)),
+            Err(Error::BaseOffsetMismatch)
+        );
+        assert_eq!(
+            Header::decode(&buffer, b"TEST", 2, None),
+            Err(Error::UnsupportedFormat)
+        );
+        assert_eq!(
+            Header::decode(&buffer, b"NOPE", 1, None),
+            Err(Error::UnsupportedFormat)
+        );
+
+        // nothing written yet
+        assert_eq!(
+            Header::decode(&[0; SEGMENT_HEADER_SIZE], b"TEST", 1, None),
+            Ok(None)
+        );
+    }
+}
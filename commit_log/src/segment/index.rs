@@ -1,21 +1,30 @@
 extern crate memmap;
 
-use self::memmap::MmapMut;
+use self::memmap::{MmapMut, MmapOptions};
+use super::map::Map;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
-use std::num;
-use std::path::PathBuf;
-use std::str::from_utf8_unchecked;
-
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::header::{self, Header};
+use crate::disk;
+use crate::format::{INDEX_HEADER_SIZE, INDEX_MAGIC, INDEX_VERSION};
+use crate::{IndexLayout, IoStats, OversizedSegment};
+use crc::crc32;
 use derive_more::From;
-use std::fmt;
 
 #[derive(Debug, From)]
 pub enum Error {
     Io(io::Error),
-    Num(num::ParseIntError),
     NoSpaceLeft,
     InvalidIndex,
+    Oversized,
+    /// The file was truncated under the mapping, e.g. by another process
+    Truncated,
+    /// Entries aren't encoded the way they're decoded, see `format`
+    Format,
+    /// The header doesn't match this index, see `Header`
+    Header(header::Error),
 }
 
 /// Index
@@ -34,16 +43,17 @@ pub enum Error {
 /// | offset-size | offset-size |...|----> time
 /// |-------------------------------|
 ///
-/// The role of the index is to provide pointers to records in the log file.
-/// Each entry of the index is 20 bytes long, 10 bytes are used for the offset address of the
-/// record in the log file, the other 10 bytes for the size of the record.
+/// With `IndexLayout::PageAligned`, the remainder of every page that can't fit a whole entry
+/// is left as padding, so entries never straddle OS pages. OS pages are a multiple of the
+/// size of an entry though, so there is no such remainder: the entries are laid out as when
+/// packed, and the size of the index is only rounded up to whole pages.
 ///
-/// e.g.:
-/// 00000001000000000020
+/// The role of the index is to provide pointers to records in the log file.
+/// Each entry of the index is 16 bytes long, 8 bytes are used for the offset address of the
+/// record in the log file, the other 8 bytes for the size of the record, both little-endian.
 ///
-/// is actually,
-/// 000000010 -> offset
-/// 000000020 -> size
+/// The first two slots hold the header, identifying the file, the version of its format and
+/// the segment it belongs to, see `Header`.
 ///
 /// Important:
 ///   Neither reads nor writes to the index are directly triggering disk-level actions.
@@ -55,47 +65,200 @@ pub struct Index {
     /// File Descriptor
     file: File,
 
-    /// Memory map buffer
-    mmap: MmapMut,
+    /// Memory map buffer, read-only once sealed
+    mmap: Map,
 
     /// Max size of the index
     max_size: usize,
 
     /// Base offset of the index across the commit-log
+    #[allow(dead_code)]
     base_offset: usize,
 
-    /// Current size of the index in bytes (used as a cursor when writing)
-    offset: usize,
+    /// Amount of entries written (used as a cursor when writing)
+    entries: usize,
+
+    /// Entries per page and page size, when entries are aligned to pages
+    pages: Option<(usize, usize)>,
+
+    /// Offset up to which the index was flushed
+    flushed: usize,
+
+    /// Amount of flushes issued
+    flushes: usize,
+
+    /// Offset up to which the index is durable on disk
+    synced: usize,
+
+    /// Sealed indexes don't accept writes
+    sealed: bool,
 }
 
-/// Amount of bytes for each entry on the index
-const ENTRY_SIZE: usize = 20;
+pub use crate::format::ENTRY_SIZE;
+
+/// Amount of entry slots taken by the header
+const HEADER_SLOTS: usize = INDEX_HEADER_SIZE / ENTRY_SIZE;
 
 impl Index {
     /// Create a new Index / reads the existing Index
-    pub fn new(path: PathBuf, base_offset: usize, max_size: usize) -> Result<Self, Error> {
+    ///
+    /// The policy decides what to do if the file on disk is bigger than `max_size`.
+    pub fn new(
+        path: PathBuf,
+        base_offset: usize,
+        max_size: usize,
+        policy: OversizedSegment,
+        layout: IndexLayout,
+    ) -> Result<Self, Error> {
+        self_check()?;
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path.join(format!("{:020}.idx", base_offset)))?; //TODO improve file formatting
 
-        file.set_len(max_size as u64).unwrap(); //TODO Should we avoid truncating when size is given?
+        let (max_size, sealed) = match policy {
+            _ if file.metadata()?.len() <= max_size as u64 => (max_size, false),
+            OversizedSegment::Error => return Err(Error::Oversized),
+            OversizedSegment::Adopt => (file.metadata()?.len() as usize, false),
+            OversizedSegment::Seal => (file.metadata()?.len() as usize, true),
+        };
+
+        file.set_len(max_size as u64)?;
+
+        let mut mmap = unsafe { MmapMut::map_mut(&file).expect("failed to map the file") };
 
-        let mmap = unsafe { MmapMut::map_mut(&file).expect("failed to map the file") };
+        // new files get a header, unless too small to hold any entry anyway
+        let header = Header::decode(&mmap, INDEX_MAGIC, INDEX_VERSION, Some(base_offset))?;
+        if header.is_none() && !sealed && max_size >= INDEX_HEADER_SIZE {
+            mmap[0..INDEX_HEADER_SIZE]
+                .copy_from_slice(&Header::new(INDEX_VERSION, base_offset).encode(INDEX_MAGIC));
+        }
 
         Ok(Self {
             base_offset,
             max_size,
-            offset: 0,
+            entries: 0,
+            pages: pages(layout),
             file,
-            mmap,
+            mmap: Map::Writable(mmap),
+            flushed: 0,
+            flushes: 0,
+            synced: 0,
+            sealed,
         })
     }
 
+    /// Open an existing index file, of the given layout, for reading only
+    ///
+    /// The file is mapped copy-on-write from a read-only descriptor, so it's never modified.
+    /// Unless the amount of entries is known (e.g. from a footer), entries are counted up to
+    /// the first slot that doesn't hold a valid entry.
+    pub fn open_read_only(
+        path: &Path,
+        base_offset: usize,
+        layout: IndexLayout,
+        entries: Option<usize>,
+    ) -> Result<Self, Error> {
+        self_check()?;
+        let file = File::open(path)?;
+        let max_size = file.metadata()?.len() as usize;
+        let mmap = unsafe { MmapOptions::new().map_copy(&file)? };
+        Header::decode(&mmap, INDEX_MAGIC, INDEX_VERSION, None)?;
+
+        let mut index = Self {
+            base_offset,
+            max_size,
+            entries: 0,
+            pages: pages(layout),
+            file,
+            mmap: Map::Writable(mmap),
+            flushed: 0,
+            flushes: 0,
+            synced: 0,
+            sealed: true,
+        };
+        match entries {
+            Some(entries) => index.restore(entries)?,
+            None => index.recover(),
+        }
+        index.flushed = index.end(index.entries);
+        index.synced = index.flushed;
+
+        Ok(index)
+    }
+
+    /// Recover the entries of an existing file, up to the first slot that doesn't hold a
+    /// valid entry
+    pub fn recover(&mut self) {
+        self.entries = 0;
+        while self.end(self.entries + 1) <= self.max_size {
+            let position = self.position(self.entries);
+            // records hold at least their header (even with an empty payload), a zero size means
+            // the slot was never written
+            if Entry::decode(&self.mmap[position..(position + ENTRY_SIZE)]).size == 0 {
+                break;
+            }
+            self.entries += 1;
+        }
+    }
+
+    /// Restore a known amount of entries of an existing file (e.g. from a footer)
+    pub fn restore(&mut self, entries: usize) -> Result<(), Error> {
+        if self.end(entries) > self.max_size {
+            return Err(Error::InvalidIndex);
+        }
+        self.entries = entries;
+        Ok(())
+    }
+
+    /// Drop the entries past the given amount, zeroing their slots so they are never recovered
+    pub fn truncate(&mut self, entries: usize) {
+        for entry in entries..self.entries {
+            let position = self.position(entry);
+            if let Some(mmap) = self.mmap.writable() {
+                mmap[position..(position + ENTRY_SIZE)].fill(0);
+            }
+        }
+        self.entries = self.entries.min(entries);
+    }
+
     /// Check if the given amount of entries fit
     pub fn fit(&mut self, entry: usize) -> bool {
-        self.max_size >= (self.offset + (entry * ENTRY_SIZE))
+        !self.sealed && self.max_size >= self.end(self.entries + entry)
+    }
+
+    /// Return the amount of entries written
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Return true if the index doesn't accept writes
+    pub fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Stop accepting writes, trimming the file down to the bytes written
+    ///
+    /// Indexes sealed from the start (e.g. oversized, or opened read-only) are left untouched.
+    pub fn seal(&mut self) -> Result<(), Error> {
+        if self.sealed {
+            return Ok(());
+        }
+        self.sealed = true;
+
+        let used = self.used();
+        self.mmap.seal(&self.file, used)?;
+        self.max_size = used;
+        self.flushed = self.end(self.entries);
+        self.synced = self.flushed;
+        Ok(())
+    }
+
+    /// Return the CRC32C of the written entries
+    pub fn crc(&self) -> u32 {
+        crc32::checksum_castagnoli(&self.mmap[0..self.end(self.entries)])
     }
 
     /// Write an entry to the index
@@ -103,40 +266,156 @@ impl Index {
         if !self.fit(1) {
             return Err(Error::NoSpaceLeft);
         }
-        self.offset += ENTRY_SIZE;
+        let position = self.position(self.entries);
+        let mmap = self.mmap.writable().ok_or(Error::NoSpaceLeft)?;
+        mmap[position..(position + ENTRY_SIZE)].copy_from_slice(&entry.encode());
+        self.entries += 1;
+
+        Ok(ENTRY_SIZE)
+    }
+
+    /// Return the byte position of the given entry
+    fn position(&self, entry: usize) -> usize {
+        let slot = entry + HEADER_SLOTS;
+        match self.pages {
+            None => slot * ENTRY_SIZE,
+            Some((per_page, page_size)) => {
+                (slot / per_page) * page_size + (slot % per_page) * ENTRY_SIZE
+            }
+        }
+    }
+
+    /// Make sure the file still holds the first `len` bytes
+    ///
+    /// Touching a mapped page past the end of the file raises a SIGBUS, killing the process,
+    /// so reads check the actual length of the file first, in case it was truncated externally.
+    fn check_len(&self, len: usize) -> Result<(), Error> {
+        if len as u64 > self.file.metadata()?.len() {
+            return Err(Error::Truncated);
+        }
+        Ok(())
+    }
+
+    /// Return the byte position right after the first `entries` entries
+    fn end(&self, entries: usize) -> usize {
+        match entries {
+            0 => 0,
+            entries => self.position(entries - 1) + ENTRY_SIZE,
+        }
+    }
 
-        let size = (&mut self.mmap[(self.offset - ENTRY_SIZE)..(self.offset)])
-            .write(entry.to_string().as_bytes())?;
-        Ok(size)
+    /// Return the amount of bytes used, the header is written along with the file
+    fn used(&self) -> usize {
+        self.end(self.entries)
+            .max(INDEX_HEADER_SIZE.min(self.max_size))
     }
 
     /// Flush to ensure the content on memory is written to the file
     pub fn flush(&mut self) -> Result<(), Error> {
         self.mmap.flush_async()?;
+        self.flushed = self.end(self.entries);
+        self.flushes += 1;
         Ok(())
     }
 
+    /// Durably flush the first `entries` entries, skipping what is already on disk
+    pub fn flush_until(&mut self, entries: usize) -> Result<(), Error> {
+        if entries > self.entries {
+            return Err(Error::InvalidIndex);
+        }
+        let offset = self.end(entries);
+        if offset <= self.synced {
+            return Ok(());
+        }
+
+        self.mmap.flush_range(self.synced, offset - self.synced)?;
+        self.file.sync_data()?;
+
+        self.synced = offset;
+        self.flushed = self.flushed.max(offset);
+        self.flushes += 1;
+        Ok(())
+    }
+
+    /// Return a snapshot of the IO performed on the index
+    pub fn io_stats(&self) -> IoStats {
+        IoStats {
+            bytes_written: self.used(),
+            bytes_flushed: self.flushed,
+            flushes: self.flushes,
+        }
+    }
+
     /// Read an entry from the index
     pub fn read_at(&self, offset: usize) -> Result<Entry, Error> {
-        let real_offset = offset * ENTRY_SIZE;
+        let real_offset = self.position(offset);
 
-        if (real_offset + ENTRY_SIZE) >= self.mmap.len() {
+        if offset >= self.entries || (real_offset + ENTRY_SIZE) > self.mmap.len() {
             return Err(Error::InvalidIndex);
         }
+        self.check_len(real_offset + ENTRY_SIZE)?;
+
+        Ok(Entry::decode(
+            &self.mmap[real_offset..(real_offset + ENTRY_SIZE)],
+        ))
+    }
 
-        let buffer = &self.mmap[real_offset..(real_offset + ENTRY_SIZE)];
+    /// Read up to `count` contiguous entries, starting at the given entry offset
+    ///
+    /// Only entries written through this index are returned, so the result is shorter than
+    /// `count` when the range goes past the last one.
+    pub fn read_range(&self, offset: usize, count: usize) -> Result<Vec<Entry>, Error> {
+        if offset > self.entries {
+            return Err(Error::InvalidIndex);
+        }
+        let end = self.entries.min(offset.saturating_add(count));
+        self.check_len(self.end(end))?;
+
+        (offset..end)
+            .map(|entry| {
+                let position = self.position(entry);
+                Ok(Entry::decode(&self.mmap[position..(position + ENTRY_SIZE)]))
+            })
+            .collect()
+    }
+}
 
-        let position = unsafe {
-            let position = from_utf8_unchecked(&buffer[0..(ENTRY_SIZE / 2)]).parse()?;
-            position
-        };
+/// Check that entries are encoded the way they're decoded, before touching any file
+fn self_check() -> Result<(), Error> {
+    let entry = Entry::new(usize::MAX, 1);
+
+    let header = Header::new(INDEX_VERSION, 0);
+
+    if Entry::decode(&entry.encode()) != entry
+        || Header::decode(
+            &header.encode(INDEX_MAGIC),
+            INDEX_MAGIC,
+            INDEX_VERSION,
+            Some(0),
+        ) != Ok(Some(header))
+    {
+        return Err(Error::Format);
+    }
+    Ok(())
+}
 
-        let size = unsafe {
-            let size = from_utf8_unchecked(&buffer[(ENTRY_SIZE / 2)..ENTRY_SIZE]).parse()?;
-            size
-        };
+/// Return the entries per page and page size of the layout, `None` when packed
+fn pages(layout: IndexLayout) -> Option<(usize, usize)> {
+    match layout {
+        IndexLayout::Packed => None,
+        IndexLayout::PageAligned => {
+            let page_size = disk::page_size();
+            Some((page_size / ENTRY_SIZE, page_size))
+        }
+    }
+}
 
-        Ok(Entry::new(position, size))
+/// Return the size in bytes of an index holding `entries` entries with the given layout
+pub fn size_for(entries: usize, layout: IndexLayout) -> usize {
+    let slots = entries + HEADER_SLOTS;
+    match pages(layout) {
+        None => slots * ENTRY_SIZE,
+        Some((per_page, page_size)) => slots.div_ceil(per_page) * page_size,
     }
 }
 
@@ -144,6 +423,7 @@ impl Index {
 ///
 /// A tuple to store the offset and size of a record present in the logfile
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry {
     /// Offset of the record
     pub offset: usize,
@@ -159,9 +439,26 @@ impl Entry {
     }
 }
 
-impl fmt::Display for Entry {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:010}{:010}", self.offset, self.size)
+impl Entry {
+    /// Return the on-disk representation of the entry
+    fn encode(&self) -> [u8; ENTRY_SIZE] {
+        let mut buffer = [0; ENTRY_SIZE];
+        buffer[0..8].copy_from_slice(&(self.offset as u64).to_le_bytes());
+        buffer[8..ENTRY_SIZE].copy_from_slice(&(self.size as u64).to_le_bytes());
+        buffer
+    }
+
+    /// Parse an entry from its on-disk representation
+    fn decode(buffer: &[u8]) -> Self {
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&buffer[0..8]);
+        let mut size = [0; 8];
+        size.copy_from_slice(&buffer[8..ENTRY_SIZE]);
+
+        Entry::new(
+            u64::from_le_bytes(offset) as usize,
+            u64::from_le_bytes(size) as usize,
+        )
     }
 }
 
@@ -175,14 +472,18 @@ mod tests {
 
     /// Entry tests
     #[test]
-    fn test_entry_to_string() {
-        let e0 = Entry::new(0, 0);
-        let e1 = Entry::new(1, 2);
-        let e2 = Entry::new(1521230, 91028317);
+    fn test_entry_encode() {
+        let e = Entry::new(1521230, 91028317);
+        let encoded = e.encode();
+
+        assert_eq!(encoded[0..8], 1521230u64.to_le_bytes());
+        assert_eq!(encoded[8..16], 91028317u64.to_le_bytes());
+        assert_eq!(Entry::decode(&encoded), e);
+    }
 
-        assert_eq!(e0.to_string(), "00000000000000000000".to_string());
-        assert_eq!(e1.to_string(), "00000000010000000002".to_string());
-        assert_eq!(e2.to_string(), "00015212300091028317".to_string());
+    #[test]
+    fn test_self_check() {
+        self_check().unwrap();
     }
 
     /// Index tests
@@ -192,15 +493,66 @@ mod tests {
         fs::create_dir_all(tmp_dir.clone()).unwrap();
         let expected_file = tmp_dir.clone().join("00000000000000000000.idx");
 
-        Index::new(tmp_dir.clone(), 0, 10).unwrap();
+        Index::new(
+            tmp_dir.clone(),
+            0,
+            10,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
 
         assert!(expected_file.as_path().exists());
     }
 
+    #[test]
+    fn test_create_oversized() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        fs::write(tmp_dir.join("00000000000000000000.idx"), [0; 64]).unwrap();
+
+        assert!(Index::new(
+            tmp_dir.clone(),
+            0,
+            48,
+            OversizedSegment::Error,
+            IndexLayout::default()
+        )
+        .is_err());
+
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            48,
+            OversizedSegment::Adopt,
+            IndexLayout::default(),
+        )
+        .unwrap();
+        assert!(i.fit(2));
+        assert!(!i.fit(3));
+
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            48,
+            OversizedSegment::Seal,
+            IndexLayout::default(),
+        )
+        .unwrap();
+        assert!(!i.fit(1));
+    }
+
     #[test]
     #[should_panic]
     fn test_invalid_create() {
-        Index::new(Path::new("/invalid/dir/").to_path_buf(), 0, 100).unwrap();
+        Index::new(
+            Path::new("/invalid/dir/").to_path_buf(),
+            0,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
     }
 
     #[test]
@@ -209,15 +561,81 @@ mod tests {
         let expected_file = tmp_dir.clone().join("00000000000000000000.idx");
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut i = Index::new(tmp_dir.clone(), 0, 25).unwrap();
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            56,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         i.write(Entry::new(0, 10)).unwrap();
         i.flush().unwrap(); // flush the file to ensure content is gonna be written
 
         // Notice that the log file is truncated with empty bytes
-        assert_eq!(
-            fs::read_to_string(expected_file).unwrap(),
-            String::from("00000000000000000010\u{0}\u{0}\u{0}\u{0}\u{0}")
+        let content = fs::read(expected_file).unwrap();
+        assert_eq!(&content[0..4], b"VIDX");
+        assert_eq!(content[4..8], 2u32.to_le_bytes());
+        assert_eq!(content[16..24], 0u64.to_le_bytes());
+        assert_eq!(content[32..48], Entry::new(0, 10).encode());
+        assert_eq!(content[48..56], [0; 8]);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let path = tmp_dir.join("00000000000000000000.idx");
+
+        // e.g. the zero-padded decimals of the previous format
+        fs::write(&path, b"00000000000000000010").unwrap();
+        let result = Index::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Header(header::Error::UnsupportedFormat))
+        ));
+        assert!(matches!(
+            Index::open_read_only(&path, 0, IndexLayout::default(), None),
+            Err(Error::Header(header::Error::UnsupportedFormat))
+        ));
+    }
+
+    #[test]
+    fn test_base_offset_mismatch() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        Index::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+
+        // e.g. a file renamed to another segment
+        fs::rename(
+            tmp_dir.join("00000000000000000000.idx"),
+            tmp_dir.join("00000000000000000042.idx"),
+        )
+        .unwrap();
+        let result = Index::new(
+            tmp_dir.clone(),
+            42,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
         );
+        assert!(matches!(
+            result,
+            Err(Error::Header(header::Error::BaseOffsetMismatch))
+        ));
     }
 
     #[test]
@@ -226,7 +644,14 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut i = Index::new(tmp_dir.clone(), 0, 10).unwrap();
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            10,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         // buffer is bigger than log size
         i.write(Entry::new(0, 10)).unwrap();
     }
@@ -236,11 +661,18 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut i = Index::new(tmp_dir.clone(), 0, 100).unwrap();
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         i.write(Entry::new(0, 10)).unwrap();
 
-        assert!(i.fit(4));
-        assert!(!i.fit(5));
+        assert!(i.fit(3));
+        assert!(!i.fit(4));
     }
 
     #[test]
@@ -248,12 +680,106 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut i = Index::new(tmp_dir.clone(), 0, 50).unwrap();
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            64,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         i.write(Entry::new(0, 10)).unwrap();
         i.write(Entry::new(10, 20)).unwrap();
 
         assert_eq!(i.read_at(0).unwrap(), Entry::new(0, 10));
         assert_eq!(i.read_at(1).unwrap(), Entry::new(10, 20));
+        assert_eq!(i.entries(), 2);
+    }
+
+    #[test]
+    fn test_page_aligned() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let page_size = disk::page_size();
+        let per_page = page_size / ENTRY_SIZE;
+        // the header takes two slots of the first page
+        let size = size_for(per_page, IndexLayout::PageAligned);
+        assert_eq!(size, 2 * page_size);
+
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            size,
+            OversizedSegment::default(),
+            IndexLayout::PageAligned,
+        )
+        .unwrap();
+        for n in 0..per_page {
+            i.write(Entry::new(n * 10, 10)).unwrap();
+        }
+
+        // the entry after a full page starts on the next one
+        let next = per_page - 2;
+        assert_eq!(i.position(next), page_size);
+        assert_eq!(i.read_at(next).unwrap(), Entry::new(next * 10, 10));
+        assert_eq!(
+            i.read_range(next - 1, 2).unwrap(),
+            vec![Entry::new((next - 1) * 10, 10), Entry::new(next * 10, 10)]
+        );
+        assert_eq!(i.io_stats().bytes_written, page_size + 2 * ENTRY_SIZE);
+        assert!(i.fit(per_page - 2));
+        assert!(!i.fit(per_page - 1));
+    }
+
+    #[test]
+    fn test_read_last_entry() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            INDEX_HEADER_SIZE + 2 * ENTRY_SIZE,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        i.write(Entry::new(0, 10)).unwrap();
+        i.write(Entry::new(10, 20)).unwrap();
+
+        assert!(!i.fit(1));
+        assert_eq!(i.read_at(1).unwrap(), Entry::new(10, 20));
+    }
+
+    #[test]
+    fn test_read_range() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        i.write(Entry::new(0, 10)).unwrap();
+        i.write(Entry::new(10, 20)).unwrap();
+        i.write(Entry::new(30, 5)).unwrap();
+
+        assert_eq!(
+            i.read_range(1, 2).unwrap(),
+            vec![Entry::new(10, 20), Entry::new(30, 5)]
+        );
+        // stops at the last written entry
+        assert_eq!(
+            i.read_range(0, 10).unwrap(),
+            vec![Entry::new(0, 10), Entry::new(10, 20), Entry::new(30, 5)]
+        );
+        assert_eq!(i.read_range(3, 1).unwrap(), vec![]);
+        assert!(i.read_range(4, 1).is_err());
     }
 
     #[test]
@@ -262,7 +788,14 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut i = Index::new(tmp_dir.clone(), 0, 50).unwrap();
+        let mut i = Index::new(
+            tmp_dir.clone(),
+            0,
+            50,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         i.write(Entry::new(0, 10)).unwrap();
 
         i.read_at(20).unwrap(); // should fail since the position is invalid
@@ -1,10 +1,18 @@
 extern crate memmap;
 
-use self::memmap::MmapMut;
+use self::memmap::{MmapMut, MmapOptions};
+use super::map::Map;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
-use std::path::PathBuf;
-
+use std::path::{Path, PathBuf};
+
+use super::header::{self, Header};
+use crate::format::{
+    CRC_SIZE, LENGTH_SIZE, LOG_HEADER_SIZE, LOG_MAGIC, LOG_VERSION, MAX_PAYLOAD_SIZE,
+    RECORD_HEADER_SIZE,
+};
+use crate::{IoStats, OversizedSegment};
+use crc::crc32;
 use derive_more::From;
 
 #[derive(Debug, From)]
@@ -12,6 +20,15 @@ pub enum Error {
     Io(io::Error),
     NoSpaceLeft,
     InvalidIndex,
+    Oversized,
+    /// The file was truncated under the mapping, e.g. by another process
+    Truncated,
+    /// The record doesn't match the CRC of its header, e.g. a torn write or bit rot
+    ChecksumMismatch,
+    /// The length prefix of the record doesn't match the size it was read with
+    LengthMismatch,
+    /// The header doesn't match this log, see `Header`
+    Header(header::Error),
 }
 
 /// Log
@@ -23,11 +40,26 @@ pub enum Error {
 /// Each new record is basically places sequencially on the byte-stream.
 ///
 /// e.g.:
-///                          current cursor
-///                                 ^
-/// |-------------------------------|
-/// | record 0  |  record 1  |  ... |----> time
-/// |-------------------------------|
+///                                   current cursor
+///                                          ^
+/// |----------------------------------------|
+/// | header | record 0  |  record 1  |  ... |----> time
+/// |----------------------------------------|
+///
+/// The header identifies the file, the version of its format and the segment it belongs to,
+/// see `Header`. Offsets are positions in the file, the first record starts right after it.
+///
+/// Each record is a small header followed by the payload:
+///
+/// |-------|--------|---------|
+/// | crc32 | length | payload |
+/// |-------|--------|---------|
+///    4B       4B
+///
+/// The length (little-endian u32) makes the log self-describing, records can be enumerated
+/// without the index (see `frame_at`). The CRC32C covers both the length and the payload,
+/// and is verified on every read. Unwritten bytes never pass for a record, not even an empty
+/// one, as the CRC of a zero length isn't zero.
 ///
 /// Important:
 ///   Neither reads nor writes to the log are directly triggering disk-level actions.
@@ -39,10 +71,11 @@ pub struct Log {
     /// File Descriptor
     file: File,
 
-    /// Memory buffer
-    mmap: MmapMut,
+    /// Memory buffer, read-only once sealed
+    mmap: Map,
 
     /// Base offset of the log on the global commit-log
+    #[allow(dead_code)]
     base_offset: usize,
 
     /// Current size of the file in bytes
@@ -50,33 +83,101 @@ pub struct Log {
 
     /// Max size of the file in bytes
     max_size: usize,
+
+    /// Length of the file when last checked, see `check_len`
+    file_len: usize,
+
+    /// Offset up to which the file was flushed
+    flushed: usize,
+
+    /// Amount of flushes issued
+    flushes: usize,
+
+    /// Offset up to which the log is durable on disk
+    synced: usize,
+
+    /// Sealed logs don't accept writes
+    sealed: bool,
 }
 
 impl Log {
     /// Create a new log file, from the scratch.
-    pub fn new(path: PathBuf, base_offset: usize, max_size: usize) -> Result<Self, Error> {
+    ///
+    /// The policy decides what to do if the file on disk is bigger than `max_size`.
+    pub fn new(
+        path: PathBuf,
+        base_offset: usize,
+        max_size: usize,
+        policy: OversizedSegment,
+    ) -> Result<Self, Error> {
         //TODO we never close this file, ...
         //TODO should we truncate the file instead of appending?
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path.join(format!("{:020}.log", base_offset)))?; //TODO improve file formatting
 
+        let (max_size, sealed) = match policy {
+            _ if file.metadata()?.len() <= max_size as u64 => (max_size, false),
+            OversizedSegment::Error => return Err(Error::Oversized),
+            OversizedSegment::Adopt => (file.metadata()?.len() as usize, false),
+            OversizedSegment::Seal => (file.metadata()?.len() as usize, true),
+        };
+
         file.set_len(max_size as u64)?;
 
-        //TODO improve this, it's zero to set the correct cursor, but if the file was opened it must be the size
-        //let size = file.metadata()?.len() as usize;
-        let offset = 0;
+        let mut mmap = unsafe { MmapMut::map_mut(&file).expect("failed to map the file") };
+
+        // new files get a header, unless too small to hold any record anyway
+        let header = Header::decode(&mmap, LOG_MAGIC, LOG_VERSION, Some(base_offset))?;
+        if header.is_none() && !sealed && max_size >= LOG_HEADER_SIZE {
+            mmap[0..LOG_HEADER_SIZE]
+                .copy_from_slice(&Header::new(LOG_VERSION, base_offset).encode(LOG_MAGIC));
+        }
 
-        let mmap = unsafe { MmapMut::map_mut(&file).expect("failed to map the file") };
+        // the cursor of an existing file is restored by the segment, see `restore`
+        let offset = LOG_HEADER_SIZE.min(max_size);
 
         Ok(Self {
             file,
             base_offset,
             offset,
             max_size,
-            mmap,
+            file_len: max_size,
+            mmap: Map::Writable(mmap),
+            flushed: 0,
+            flushes: 0,
+            synced: 0,
+            sealed,
+        })
+    }
+
+    /// Open an existing log file for reading only, `size` being the amount of bytes written
+    ///
+    /// The file is mapped copy-on-write from a read-only descriptor, so it's never modified.
+    pub fn open_read_only(path: &Path, base_offset: usize, size: usize) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let max_size = file.metadata()?.len() as usize;
+        if size > max_size {
+            return Err(Error::InvalidIndex);
+        }
+
+        let mmap = unsafe { MmapOptions::new().map_copy(&file)? };
+        Header::decode(&mmap, LOG_MAGIC, LOG_VERSION, None)?;
+
+        Ok(Self {
+            file,
+            base_offset,
+            offset: size,
+            max_size,
+            file_len: max_size,
+            mmap: Map::Writable(mmap),
+            flushed: size,
+            flushes: 0,
+            synced: size,
+            sealed: true,
         })
     }
 
@@ -85,38 +186,208 @@ impl Log {
         self.offset
     }
 
-    /// Check is a given buffer size fits in this log-file
+    /// Return the position of the first record, right after the header
+    pub fn start(&self) -> usize {
+        LOG_HEADER_SIZE.min(self.max_size)
+    }
+
+    /// Restore the cursor of an existing file, `offset` being the amount of bytes written
+    pub fn restore(&mut self, offset: usize) -> Result<(), Error> {
+        if offset > self.max_size {
+            return Err(Error::InvalidIndex);
+        }
+        self.offset = offset;
+        Ok(())
+    }
+
+    /// Zero the bytes between the cursor and `end` (e.g. left by a torn write), so they are
+    /// never taken for records
+    pub fn clear_until(&mut self, end: usize) {
+        let offset = self.offset;
+        if let Some(mmap) = self.mmap.writable() {
+            let end = end.min(mmap.len());
+            if end > offset {
+                mmap[offset..end].fill(0);
+            }
+        }
+    }
+
+    /// Return true if the log doesn't accept writes
+    pub fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Stop accepting writes, trimming the file down to the bytes written
+    ///
+    /// Logs sealed from the start (e.g. oversized, or opened read-only) are left untouched.
+    pub fn seal(&mut self) -> Result<(), Error> {
+        if self.sealed {
+            return Ok(());
+        }
+        self.sealed = true;
+
+        self.mmap.seal(&self.file, self.offset)?;
+        self.max_size = self.offset;
+        self.file_len = self.offset;
+        self.flushed = self.offset;
+        self.synced = self.offset;
+        Ok(())
+    }
+
+    /// Check is a given buffer size fits in this log-file, along with its header
     pub fn fit(&mut self, buffer_size: usize) -> bool {
-        (self.max_size - self.offset) >= buffer_size
+        !self.sealed
+            && buffer_size <= MAX_PAYLOAD_SIZE
+            && (self.max_size - self.offset) >= frame_size(buffer_size)
     }
 
     /// Flush to ensure the content on memory is written to the file
     pub fn flush(&mut self) -> Result<(), Error> {
         self.mmap.flush_async()?;
+        self.check_len()?;
+        self.flushed = self.offset;
+        self.flushes += 1;
         Ok(())
     }
 
-    /// Write a buffer to the log-file
+    /// Durably flush the log up to the given offset, skipping what is already on disk
+    pub fn flush_until(&mut self, offset: usize) -> Result<(), Error> {
+        if offset <= self.synced {
+            return Ok(());
+        }
+        if offset > self.offset {
+            return Err(Error::InvalidIndex);
+        }
+
+        self.mmap.flush_range(self.synced, offset - self.synced)?;
+        self.file.sync_data()?;
+        self.check_len()?;
+
+        self.synced = offset;
+        self.flushed = self.flushed.max(offset);
+        self.flushes += 1;
+        Ok(())
+    }
+
+    /// Return a snapshot of the IO performed on the log-file
+    pub fn io_stats(&self) -> IoStats {
+        IoStats {
+            bytes_written: self.offset,
+            bytes_flushed: self.flushed,
+            flushes: self.flushes,
+        }
+    }
+
+    /// Write a buffer to the log-file, returning the size of the record (header included)
     pub fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
-        let buffer_size = buffer.len();
-        if !self.fit(buffer_size) {
+        let size = frame_size(buffer.len());
+        if !self.fit(buffer.len()) {
             return Err(Error::NoSpaceLeft);
         }
 
-        self.offset += buffer_size;
-        let size = (&mut self.mmap[(self.offset - buffer_size)..(self.offset)]).write(buffer)?;
+        let start = self.offset;
+        let mmap = self.mmap.writable().ok_or(Error::NoSpaceLeft)?;
+        mmap[(start + CRC_SIZE)..(start + RECORD_HEADER_SIZE)]
+            .copy_from_slice(&(buffer.len() as u32).to_le_bytes());
+        (&mut mmap[(start + RECORD_HEADER_SIZE)..(start + size)]).write_all(buffer)?;
+        let crc = crc32::checksum_castagnoli(&mmap[(start + CRC_SIZE)..(start + size)]);
+        mmap[start..(start + CRC_SIZE)].copy_from_slice(&crc.to_le_bytes());
+        self.offset += size;
+
         Ok(size)
     }
 
     //TODO read from the segment mmap reader
-    /// Read the log on a specific position
+    /// Read the payload of the record at a specific position, `size` being the size of the
+    /// record (header included)
+    ///
+    /// Touching a mapped page past the end of the file raises a SIGBUS, killing the process,
+    /// so reads are checked against the length of the file (see `check_len`). This narrows
+    /// the window for an external truncation to crash a reader, but can't close it entirely.
     pub fn read_at(&self, offset: usize, size: usize) -> Result<&[u8], Error> {
-        if (offset + size) > self.mmap.len() {
+        if size < RECORD_HEADER_SIZE {
+            return Err(Error::InvalidIndex);
+        }
+        let record = self.read_raw(offset, size)?;
+
+        if frame_size(length(record)) != size {
+            return Err(Error::LengthMismatch);
+        }
+        if !checksum_matches(record) {
+            return Err(Error::ChecksumMismatch);
+        }
+
+        Ok(&record[RECORD_HEADER_SIZE..])
+    }
+
+    /// Return the size of the record at a specific position (header included), from its
+    /// length prefix, or `None` if there is no valid record there (e.g. past the last one)
+    pub fn frame_at(&self, offset: usize) -> Result<Option<usize>, Error> {
+        let header = match self.read_raw(offset, RECORD_HEADER_SIZE) {
+            Ok(header) => header,
+            Err(Error::InvalidIndex) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let size = frame_size(length(header));
+        match self.read_raw(offset, size) {
+            Ok(record) if checksum_matches(record) => Ok(Some(size)),
+            Ok(_) | Err(Error::InvalidIndex) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read `size` bytes at a specific position, as they are laid out in the log (headers
+    /// included, nothing checked)
+    pub fn read_raw(&self, offset: usize, size: usize) -> Result<&[u8], Error> {
+        let end = offset.checked_add(size).ok_or(Error::InvalidIndex)?;
+        if end > self.mmap.len() {
             return Err(Error::InvalidIndex);
         }
+        if end > self.file_len {
+            return Err(Error::Truncated);
+        }
 
-        Ok(&self.mmap[(offset)..(offset + size)])
+        Ok(&self.mmap[offset..end])
     }
+
+    /// Refresh the length of the file reads are checked against
+    ///
+    /// A syscall, so it's done when the file is mapped and flushed rather than on every read:
+    /// an external truncation is noticed by the next flush.
+    fn check_len(&mut self) -> Result<(), Error> {
+        self.file_len = self.file.metadata()?.len() as usize;
+        if self.file_len < self.offset {
+            return Err(Error::Truncated);
+        }
+        Ok(())
+    }
+}
+
+/// Return true if the whole record (header included) matches its length prefix and CRC
+pub fn is_intact(record: &[u8]) -> bool {
+    record.len() >= RECORD_HEADER_SIZE
+        && frame_size(length(record)) == record.len()
+        && checksum_matches(record)
+}
+
+/// Return the length prefix of the record (or its header)
+fn length(record: &[u8]) -> usize {
+    let mut length = [0; LENGTH_SIZE];
+    length.copy_from_slice(&record[CRC_SIZE..RECORD_HEADER_SIZE]);
+    u32::from_le_bytes(length) as usize
+}
+
+/// Return true if the CRC of the record matches its length and payload
+fn checksum_matches(record: &[u8]) -> bool {
+    let mut crc = [0; CRC_SIZE];
+    crc.copy_from_slice(&record[0..CRC_SIZE]);
+    crc32::checksum_castagnoli(&record[CRC_SIZE..]) == u32::from_le_bytes(crc)
+}
+
+/// Return the size of the record holding a payload of the given size (header included)
+pub fn frame_size(payload_size: usize) -> usize {
+    RECORD_HEADER_SIZE + payload_size
 }
 
 #[cfg(test)]
@@ -133,16 +404,73 @@ mod tests {
         fs::create_dir_all(tmp_dir.clone()).unwrap();
         let expected_file = tmp_dir.clone().join("00000000000000000000.log");
 
-        let l = Log::new(tmp_dir.clone(), 0, 10).unwrap();
+        let l = Log::new(tmp_dir.clone(), 0, 40, OversizedSegment::default()).unwrap();
 
         assert!(expected_file.as_path().exists());
-        assert_eq!(l.offset(), 0); // should be zero when creating
+        assert_eq!(l.offset(), 32); // should be right after the header when creating
+    }
+
+    #[test]
+    fn test_create_oversized() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let expected_file = tmp_dir.clone().join("00000000000000000000.log");
+        Log::new(tmp_dir.clone(), 0, 57, OversizedSegment::default()).unwrap();
+
+        assert!(Log::new(tmp_dir.clone(), 0, 40, OversizedSegment::Error).is_err());
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 40, OversizedSegment::Adopt).unwrap();
+        assert!(l.fit(17)); // along with its 8 bytes header, after the 32 bytes of the file's
+        assert!(!l.fit(18));
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 40, OversizedSegment::Seal).unwrap();
+        assert!(!l.fit(1));
+
+        // the file is never truncated
+        assert_eq!(fs::metadata(expected_file).unwrap().len(), 57);
+    }
+
+    #[test]
+    fn test_header() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let path = tmp_dir.join("00000000000000000042.log");
+
+        Log::new(tmp_dir.clone(), 42, 100, OversizedSegment::default()).unwrap();
+        let content = fs::read(&path).unwrap();
+        assert_eq!(&content[0..4], b"VLOG");
+        assert_eq!(content[4..8], 1u32.to_le_bytes());
+        assert_eq!(content[16..24], 42u64.to_le_bytes());
+
+        // e.g. a file renamed to another segment
+        fs::rename(&path, tmp_dir.join("00000000000000000007.log")).unwrap();
+        assert!(matches!(
+            Log::new(tmp_dir.clone(), 7, 100, OversizedSegment::default()),
+            Err(Error::Header(header::Error::BaseOffsetMismatch))
+        ));
+
+        // e.g. the log of the previous format, records from the first byte
+        fs::write(&path, b"this-has-17-bytes-and-some-more-to-fill-a-header").unwrap();
+        assert!(matches!(
+            Log::new(tmp_dir.clone(), 42, 100, OversizedSegment::default()),
+            Err(Error::Header(header::Error::UnsupportedFormat))
+        ));
+        assert!(matches!(
+            Log::open_read_only(&path, 42, 0),
+            Err(Error::Header(header::Error::UnsupportedFormat))
+        ));
     }
 
     #[test]
     #[should_panic]
     fn test_invalid_create() {
-        Log::new(Path::new("/invalid/dir/").to_path_buf(), 0, 100).unwrap();
+        Log::new(
+            Path::new("/invalid/dir/").to_path_buf(),
+            0,
+            100,
+            OversizedSegment::default(),
+        )
+        .unwrap();
     }
 
     #[test]
@@ -151,17 +479,42 @@ mod tests {
         let expected_file = tmp_dir.clone().join("00000000000000000000.log");
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut l = Log::new(tmp_dir.clone(), 0, 20).unwrap();
-        l.write(b"this-has-17-bytes").unwrap();
+        let mut l = Log::new(tmp_dir.clone(), 0, 61, OversizedSegment::default()).unwrap();
+        assert_eq!(l.write(b"this-has-17-bytes").unwrap(), 25);
         l.flush().unwrap(); // flush the file to ensure content is gonna be written
 
         // Notice that the log file is truncated with empty bytes
+        let content = fs::read(expected_file).unwrap();
+        assert_eq!(&content[0..4], b"VLOG");
         assert_eq!(
-            fs::read_to_string(expected_file).unwrap(),
-            String::from("this-has-17-bytes\u{0}\u{0}\u{0}")
+            content[32..36],
+            crc32::checksum_castagnoli(&content[36..57]).to_le_bytes()
         );
+        assert_eq!(content[36..40], 17u32.to_le_bytes());
+        assert_eq!(&content[40..], b"this-has-17-bytes\0\0\0\0");
 
-        assert_eq!(l.offset(), 17); // should update the offset when writing
+        assert_eq!(l.offset(), 57); // should update the offset when writing
+    }
+
+    #[test]
+    fn test_flush_until() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 82, OversizedSegment::default()).unwrap();
+        l.write(b"this-has-17-bytes").unwrap();
+        l.write(b"second").unwrap();
+
+        l.flush_until(57).unwrap();
+        assert_eq!(l.io_stats().bytes_flushed, 57);
+        assert_eq!(l.io_stats().flushes, 1);
+
+        // already durable, nothing to do
+        l.flush_until(42).unwrap();
+        assert_eq!(l.io_stats().flushes, 1);
+
+        // can't flush what wasn't written
+        assert!(l.flush_until(72).is_err());
     }
 
     #[test]
@@ -170,7 +523,7 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut l = Log::new(tmp_dir.clone(), 0, 15).unwrap();
+        let mut l = Log::new(tmp_dir.clone(), 0, 47, OversizedSegment::default()).unwrap();
         // buffer is bigger than log size
         l.write(b"this-has-17-bytes").unwrap();
     }
@@ -180,14 +533,15 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut l = Log::new(tmp_dir.clone(), 0, 100).unwrap();
+        let mut l = Log::new(tmp_dir.clone(), 0, 132, OversizedSegment::default()).unwrap();
         l.write(b"this-has-17-bytes").unwrap();
 
-        assert!(l.fit(20)); //  20 =< (100 - 17)
-        assert!(l.fit(82)); //  82 =< (100 - 17)
-        assert!(l.fit(83)); //  83 =< (100 - 17)
-        assert!(!l.fit(84)); //  84 =< (100 - 17)
-        assert!(!l.fit(200)); // 200 =< (100 - 17)
+        // records take 8 more bytes for the header, after the 32 bytes of the file's
+        assert!(l.fit(20)); //  8 + 20 =< (132 - 32 - 25)
+        assert!(l.fit(66)); //  8 + 66 =< (132 - 32 - 25)
+        assert!(l.fit(67)); //  8 + 67 =< (132 - 32 - 25)
+        assert!(!l.fit(68)); //  8 + 68 =< (132 - 32 - 25)
+        assert!(!l.fit(200)); // 8 + 200 =< (132 - 32 - 25)
     }
 
     #[test]
@@ -195,12 +549,50 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut l = Log::new(tmp_dir.clone(), 0, 50).unwrap();
+        let mut l = Log::new(tmp_dir.clone(), 0, 82, OversizedSegment::default()).unwrap();
         l.write(b"hello-from-the-other-side").unwrap();
+        l.write(b"second").unwrap();
         l.flush().unwrap();
 
-        assert_eq!(l.read_at(0, 25).unwrap(), b"hello-from-the-other-side");
-        assert_eq!(l.read_at(1, 24).unwrap(), b"ello-from-the-other-side");
+        assert_eq!(l.read_at(32, 33).unwrap(), b"hello-from-the-other-side");
+        assert_eq!(l.read_at(65, 14).unwrap(), b"second");
+        assert!(l.read_at(32, 3).is_err());
+        assert!(l.read_at(0, 33).is_err()); // the header isn't a record
+    }
+
+    #[test]
+    fn test_frame_at() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 100, OversizedSegment::default()).unwrap();
+        l.write(b"hello-from-the-other-side").unwrap();
+        l.write(b"").unwrap();
+
+        // records are found from their length prefix alone
+        assert_eq!(l.frame_at(32).unwrap(), Some(33));
+        assert_eq!(l.frame_at(65).unwrap(), Some(8));
+
+        // unwritten bytes, and positions past the end, don't hold records
+        assert_eq!(l.frame_at(73).unwrap(), None);
+        assert_eq!(l.frame_at(96).unwrap(), None);
+        assert_eq!(l.frame_at(33).unwrap(), None);
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 82, OversizedSegment::default()).unwrap();
+        l.write(b"hello-from-the-other-side").unwrap();
+
+        // a record read with the wrong boundaries
+        assert!(matches!(l.read_at(32, 32), Err(Error::LengthMismatch)));
+
+        // a flipped bit
+        l.mmap.writable().unwrap()[42] ^= 1;
+        assert!(matches!(l.read_at(32, 33), Err(Error::ChecksumMismatch)));
     }
 
     #[test]
@@ -209,9 +601,34 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut l = Log::new(tmp_dir.clone(), 0, 50).unwrap();
+        let mut l = Log::new(tmp_dir.clone(), 0, 82, OversizedSegment::default()).unwrap();
+        l.write(b"hello-from-the-other-side").unwrap();
+
+        l.read_at(83, 20).unwrap(); // should fail since the position is invalid
+    }
+
+    #[test]
+    fn test_truncated_read() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut l = Log::new(tmp_dir.clone(), 0, 82, OversizedSegment::default()).unwrap();
         l.write(b"hello-from-the-other-side").unwrap();
+        l.flush().unwrap();
 
-        l.read_at(51, 20).unwrap(); // should fail since the position is invalid
+        // truncated by someone else while mapped, noticed by the next flush
+        OpenOptions::new()
+            .write(true)
+            .open(tmp_dir.join("00000000000000000000.log"))
+            .unwrap()
+            .set_len(42)
+            .unwrap();
+
+        assert!(matches!(l.flush(), Err(Error::Truncated)));
+        assert!(matches!(l.read_at(32, 33), Err(Error::Truncated)));
+        assert!(matches!(
+            l.read_raw(usize::MAX, 8),
+            Err(Error::InvalidIndex)
+        ));
     }
 }
@@ -0,0 +1,74 @@
+extern crate memmap;
+
+use self::memmap::{Mmap, MmapMut, MmapOptions};
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+/// Map
+///
+/// The memory map of a log or index file, writable until the file is sealed. Sealing trims
+/// the file down to the bytes actually used and maps it read-only, so sealed segments neither
+/// waste the space they were pre-allocated nor can be mutated by accident.
+#[derive(Debug)]
+pub enum Map {
+    Writable(MmapMut),
+    ReadOnly(Mmap),
+}
+
+impl Map {
+    /// Return the buffer to write to, `None` once sealed
+    pub fn writable(&mut self) -> Option<&mut MmapMut> {
+        match self {
+            Map::Writable(mmap) => Some(mmap),
+            Map::ReadOnly(_) => None,
+        }
+    }
+
+    /// Flush the whole buffer, asynchronously
+    pub fn flush_async(&self) -> io::Result<()> {
+        match self {
+            Map::Writable(mmap) => mmap.flush_async(),
+            // nothing was written since it was sealed
+            Map::ReadOnly(_) => Ok(()),
+        }
+    }
+
+    /// Durably flush `len` bytes starting at `offset`
+    pub fn flush_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        match self {
+            Map::Writable(mmap) => mmap.flush_range(offset, len),
+            Map::ReadOnly(_) => Ok(()),
+        }
+    }
+
+    /// Durably flush the buffer, trim the file down to `len` bytes and map it read-only
+    pub fn seal(&mut self, file: &File, len: usize) -> io::Result<()> {
+        let mmap = match self {
+            Map::Writable(mmap) => mmap,
+            Map::ReadOnly(_) => return Ok(()),
+        };
+        // empty maps aren't supported, e.g. a file too small to hold its header
+        if len == 0 {
+            return Ok(());
+        }
+
+        mmap.flush()?;
+        file.set_len(len as u64)?;
+        file.sync_all()?;
+        *self = Map::ReadOnly(unsafe { MmapOptions::new().len(len).map(file)? });
+
+        Ok(())
+    }
+}
+
+impl Deref for Map {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Map::Writable(mmap) => mmap,
+            Map::ReadOnly(mmap) => mmap,
+        }
+    }
+}
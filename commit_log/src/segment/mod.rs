@@ -1,10 +1,21 @@
+mod footer;
+mod header;
 mod index;
 mod log;
+mod map;
 
+use self::footer::Footer;
+pub use self::index::size_for as index_size_for;
+pub use self::index::Entry;
 use self::index::Index;
 use self::log::Log;
+pub use self::log::{frame_size, is_intact};
+use crate::format::LOG_HEADER_SIZE;
+use crate::latency::{Latency, Stage};
+use crate::{IndexLayout, IoStats, OversizedSegment};
+use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use derive_more::From;
 
@@ -13,6 +24,29 @@ pub enum Error {
     Io(io::Error),
     Index(index::Error),
     Log(log::Error),
+    #[from(ignore)]
+    Invariant(usize, Violation),
+}
+
+/// Violation
+///
+/// An inconsistency between the index entries and the log, found when verifying a segment.
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+    /// The entry points to a record smaller than its header (e.g. an unwritten slot)
+    ZeroSize,
+    /// The entry doesn't start after the previous one
+    NotIncreasing,
+    /// The entry starts before the previous record ends
+    Overlap,
+    /// The entry starts after the previous record ends, leaving a gap in the log
+    Gap,
+    /// The entry points past the data written to the log
+    OutOfBounds,
+    /// The record doesn't match its CRC, e.g. a torn write or bit rot
+    ChecksumMismatch,
+    /// The length prefix of the record doesn't match the size of the entry
+    LengthMismatch,
 }
 
 /// Segment
@@ -24,6 +58,8 @@ pub enum Error {
 /// 00000000000011812312.log
 /// 00000000000011812312.idx
 ///
+/// Once sealed, a footer is also written next to them (see `Footer`).
+///
 /// The role of the segment is to manage writes to the logfile and ensure
 /// the entries can be read later on by doing lookups on the index.
 ///
@@ -43,35 +79,187 @@ pub struct Segment {
 
     /// Offset (Only used as name of the file at the moment)
     offset: usize,
+
+    /// Directory of the segment files
+    path: PathBuf,
+
+    /// Path of the footer written on seal, `None` for read-only segments
+    footer: Option<PathBuf>,
 }
 
 impl Segment {
-    /// Return a new segment
+    /// Return a new segment, or reopen the existing one
+    ///
+    /// The policy decides what to do if its files on disk are bigger than the given sizes.
+    ///
+    /// Existing files are picked up where they were left: a segment with a valid footer is
+    /// opened sealed, read-only and at the size it was trimmed to. Otherwise its entries are
+    /// recovered by scanning the index, the last ones pointing to records that aren't intact
+    /// (torn by a crash mid write) are truncated, and the log cursor restored right after the
+    /// last intact record.
     pub fn new(
         path: PathBuf,
         offset: usize,
         max_log_size: usize,
         max_index_size: usize,
+        policy: OversizedSegment,
+        layout: IndexLayout,
     ) -> Result<Self, Error> {
+        let log_path = path.join(format!("{:020}.log", offset));
+        let index_path = path.join(format!("{:020}.idx", offset));
+        let footer_path = path.join(format!("{:020}.ftr", offset));
+
+        if let Some((log, index)) = Self::open_sealed(&log_path, &index_path, offset, layout)? {
+            if policy == OversizedSegment::Error {
+                if fs::metadata(&log_path)?.len() > max_log_size as u64 {
+                    return Err(Error::Log(log::Error::Oversized));
+                }
+                if fs::metadata(&index_path)?.len() > max_index_size as u64 {
+                    return Err(Error::Index(index::Error::Oversized));
+                }
+            }
+
+            return Ok(Self {
+                log,
+                index,
+                offset,
+                path,
+                footer: Some(footer_path),
+            });
+        }
+
+        let mut log = Log::new(path.clone(), offset, max_log_size, policy)?;
+        let mut index = Index::new(path.clone(), offset, max_index_size, policy, layout)?;
+        index.recover();
+        let size = truncate_torn(&mut log, &mut index)?;
+        log.restore(size)?;
+
         Ok(Self {
-            log: Log::new(path.clone(), offset, max_log_size)?,
-            index: Index::new(path, offset, max_index_size)?,
+            log,
+            index,
             offset,
+            path,
+            footer: Some(footer_path),
         })
     }
 
+    /// Open the files of an existing segment for reading only, without a CommitLog
+    ///
+    /// Meant for tooling processing segment files independently (e.g. in parallel across
+    /// machines). The segment is sealed, and its files are never modified. `layout` is the one
+    /// the index was written with, see `Manifest`.
+    ///
+    /// A segment with a valid footer is trusted as is. Otherwise it wasn't sealed cleanly, its
+    /// entries are counted by scanning the index and the segment is verified before use.
+    pub fn open_read_only<P: AsRef<Path>>(
+        log_path: P,
+        index_path: P,
+        layout: IndexLayout,
+    ) -> Result<Self, Error> {
+        let (log_path, index_path) = (log_path.as_ref(), index_path.as_ref());
+        let path = log_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        // segment files are named after their offset, e.g. 00000000000000000001.log
+        let offset = log_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+            .unwrap_or(0);
+
+        if let Some((log, index)) = Self::open_sealed(log_path, index_path, offset, layout)? {
+            return Ok(Self {
+                log,
+                index,
+                offset,
+                path,
+                footer: None,
+            });
+        }
+
+        let index = Index::open_read_only(index_path, offset, layout, None)?;
+        let size = match index.entries() {
+            0 => LOG_HEADER_SIZE,
+            entries => {
+                let last = index.read_at(entries - 1)?;
+                last.offset + last.size
+            }
+        };
+
+        let segment = Self {
+            log: Log::open_read_only(log_path, offset, size)?,
+            index,
+            offset,
+            path,
+            footer: None,
+        };
+        segment.verify()?;
+
+        Ok(segment)
+    }
+
+    /// Open the files of a segment sealed cleanly for reading only, as they are, `None` unless
+    /// it has a valid footer
+    fn open_sealed(
+        log_path: &Path,
+        index_path: &Path,
+        offset: usize,
+        layout: IndexLayout,
+    ) -> Result<Option<(Log, Index)>, Error> {
+        let footer = match Footer::read(&log_path.with_extension("ftr"))? {
+            Some(footer) => footer,
+            None => return Ok(None),
+        };
+        let index = match Index::open_read_only(index_path, offset, layout, Some(footer.entries)) {
+            Ok(index) if index.crc() == footer.index_crc => index,
+            _ => return Ok(None),
+        };
+
+        Ok(Some((
+            Log::open_read_only(log_path, offset, footer.size)?,
+            index,
+        )))
+    }
+
+    /// Return true if the segment doesn't accept writes
+    pub fn is_sealed(&self) -> bool {
+        self.log.is_sealed() || self.index.is_sealed()
+    }
+
     /// Return true if both the log and the index support the given buffer
     pub fn fit(&mut self, buffer_size: usize) -> bool {
         self.log.fit(buffer_size) && self.index.fit(1)
     }
 
+    /// Return the amount of records written to the segment
+    pub fn entries(&self) -> usize {
+        self.index.entries()
+    }
+
+    /// Return the amount of bytes written to the log, its header included
+    pub fn size(&self) -> usize {
+        self.log.offset()
+    }
+
     /// Write the buffer to the log, also making sure to create an index entry
-    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
-        self.index
-            .write(index::Entry::new(self.log.offset(), buffer.len()))?;
+    ///
+    /// The record is written before its entry, so an entry never points to missing data.
+    pub fn write(&mut self, buffer: &[u8], latency: &mut Latency) -> Result<usize, Error> {
+        if !self.index.fit(1) {
+            return Err(Error::Index(index::Error::NoSpaceLeft));
+        }
+
+        let start = latency.start();
+        let position = self.log.offset();
+        let size = self.log.write(buffer)?;
+        latency.record(Stage::LogWrite, start);
 
-        let len = self.log.write(buffer)?;
-        Ok(len)
+        let start = latency.start();
+        if let Err(e) = self.index.write(index::Entry::new(position, size)) {
+            // leave no record without an entry behind
+            self.log.restore(position)?;
+            return Err(e.into());
+        }
+        latency.record(Stage::IndexWrite, start);
+        Ok(buffer.len())
     }
 
     /// Read the log at a given index offset
@@ -82,13 +270,235 @@ impl Segment {
         Ok(buf)
     }
 
-    /// Flush both the index and the log to ensure persistence
+    /// Return the byte position and size, in the log file, of the payload of the record at a
+    /// given index offset
+    pub fn locate(&self, offset: usize) -> Result<(usize, usize), Error> {
+        if offset >= self.entries() {
+            return Err(Error::Index(index::Error::InvalidIndex));
+        }
+
+        let entry = self.index.read_at(offset)?;
+        let header = log::frame_size(0);
+        match (
+            entry.offset.checked_add(header),
+            entry.size.checked_sub(header),
+        ) {
+            (Some(start), Some(size)) => Ok((start, size)),
+            _ => Err(Error::Log(log::Error::InvalidIndex)),
+        }
+    }
+
+    /// Return the directory of the segment files
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Point the segment to the directory its files were moved to
+    ///
+    /// Open files are kept across renames, only the footer written on seal needs the new path.
+    pub fn relocate(&mut self, path: PathBuf) {
+        if let Some(footer) = &mut self.footer {
+            *footer = path.join(format!("{:020}.ftr", self.offset));
+        }
+        self.path = path;
+    }
+
+    /// Return the name of the log file
+    pub fn log_file_name(&self) -> String {
+        format!("{:020}.log", self.offset)
+    }
+
+    /// Read up to `count` records, starting at a given index offset
+    pub fn read_many(&self, offset: usize, count: usize) -> Result<Vec<&[u8]>, Error> {
+        let mut bufs = Vec::with_capacity(count.min(self.entries().saturating_sub(offset)));
+        for entry in self.index.read_range(offset, count)? {
+            bufs.push(self.log.read_at(entry.offset, entry.size)?);
+        }
+
+        Ok(bufs)
+    }
+
+    /// Read the records starting at a given index offset, as a contiguous slice of the log,
+    /// along with the size of each record (header included)
+    ///
+    /// Records are read until the next one would go past `max_bytes`, at least one record is
+    /// read (if available) so repeated calls always make progress. Every record is checked
+    /// against its CRC before being returned.
+    pub fn fetch(&self, offset: usize, max_bytes: usize) -> Result<(&[u8], Vec<usize>), Error> {
+        let mut start = None;
+        let mut bytes = 0;
+        let mut sizes = Vec::new();
+
+        for entry in offset..self.entries() {
+            let entry = self.index.read_at(entry)?;
+            if !sizes.is_empty() && bytes + entry.size > max_bytes {
+                break;
+            }
+
+            self.log.read_at(entry.offset, entry.size)?;
+            start.get_or_insert(entry.offset);
+            bytes += entry.size;
+            sizes.push(entry.size);
+        }
+
+        let buf = match start {
+            Some(start) => self.log.read_raw(start, bytes)?,
+            None => &[],
+        };
+        Ok((buf, sizes))
+    }
+
+    /// Enumerate the records of the log from their framing alone, without the index
+    ///
+    /// Returns the position and size (header included) of each record, stopping at the first
+    /// position not holding a valid one. Meant for tooling and index rebuilds.
+    pub fn scan_log(&self) -> Result<Vec<(usize, usize)>, Error> {
+        let mut records = Vec::new();
+        let mut position = self.log.start();
+        while let Some(size) = self.log.frame_at(position)? {
+            records.push((position, size));
+            position += size;
+        }
+
+        Ok(records)
+    }
+
+    /// Verify the invariants between the index and the log:
+    ///
+    /// * entries point to non-empty records
+    /// * entries are strictly increasing and contiguous (no overlaps nor gaps)
+    /// * entries never point past the data written to the log
+    /// * records match their length prefix and CRC
+    ///
+    /// The first violation is returned as `Error::Invariant(entry, violation)`.
+    pub fn verify(&self) -> Result<(), Error> {
+        let entries = self.index.read_range(0, self.entries())?;
+        let mut previous: Option<&index::Entry> = None;
+        // where the previous record ends, within the log as it was checked
+        let mut end = self.log.start();
+
+        for (i, entry) in entries.iter().enumerate() {
+            let violation = if entry.size < log::frame_size(0) {
+                Some(Violation::ZeroSize)
+            } else if previous.is_some_and(|p| entry.offset <= p.offset) {
+                Some(Violation::NotIncreasing)
+            } else if entry.offset < end {
+                Some(Violation::Overlap)
+            } else if entry.offset > end {
+                Some(Violation::Gap)
+            } else if entry
+                .offset
+                .checked_add(entry.size)
+                .is_none_or(|end| end > self.log.offset())
+            {
+                Some(Violation::OutOfBounds)
+            } else {
+                match self.log.read_at(entry.offset, entry.size) {
+                    Err(log::Error::ChecksumMismatch) => Some(Violation::ChecksumMismatch),
+                    Err(log::Error::LengthMismatch) => Some(Violation::LengthMismatch),
+                    other => other.map(|_| None)?,
+                }
+            };
+
+            if let Some(violation) = violation {
+                return Err(Error::Invariant(i, violation));
+            }
+            previous = Some(entry);
+            end = entry.offset + entry.size;
+        }
+
+        Ok(())
+    }
+
+    /// Flush both the log and the index to ensure persistence
+    ///
+    /// The log is flushed first, but flushes are asynchronous and the OS may write pages back
+    /// in any order (even before any flush). Use `flush_until` or `sync` when a durable entry
+    /// must never point to data that didn't reach the disk.
     pub fn flush(&mut self) -> Result<(), Error> {
-        self.index.flush()?;
         self.log.flush()?;
+        self.index.flush()?;
 
         Ok(())
     }
+
+    /// Durably flush every record up to (and including) the given index offset
+    ///
+    /// The log is flushed before the index, so a durable index entry never points to data
+    /// that didn't reach the disk.
+    pub fn flush_until(&mut self, offset: usize) -> Result<(), Error> {
+        if offset >= self.entries() {
+            return Err(Error::Index(index::Error::InvalidIndex));
+        }
+
+        let entry = self.index.read_at(offset)?;
+        self.log.flush_until(entry.offset + entry.size)?;
+        self.index.flush_until(offset + 1)?;
+
+        Ok(())
+    }
+
+    /// Durably flush every record written to the segment
+    pub fn sync(&mut self) -> Result<(), Error> {
+        match self.entries() {
+            0 => Ok(()),
+            entries => self.flush_until(entries - 1),
+        }
+    }
+
+    /// Durably flush the segment and stop accepting writes, writing its footer
+    pub fn seal(&mut self) -> Result<(), Error> {
+        self.sync()?;
+        self.log.seal()?;
+        self.index.seal()?;
+
+        if let Some(path) = &self.footer {
+            let footer = Footer {
+                entries: self.entries(),
+                size: self.size(),
+                index_crc: self.index.crc(),
+            };
+            footer.write(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Return a snapshot of the IO performed on both the index and the log
+    pub fn io_stats(&self) -> IoStats {
+        self.index.io_stats() + self.log.io_stats()
+    }
+}
+
+/// Truncate the last entries pointing to records that aren't intact (e.g. torn by a crash mid
+/// write), wiping them from both files, and return the size of the log up to the last intact
+/// record
+fn truncate_torn(log: &mut Log, index: &mut Index) -> Result<usize, Error> {
+    let mut torn_end = 0;
+    while index.entries() > 0 {
+        let last = index.read_at(index.entries() - 1)?;
+        match log.read_at(last.offset, last.size) {
+            Ok(_) => break,
+            Err(log::Error::Io(e)) => return Err(e.into()),
+            Err(e) => {
+                ::log::warn!("truncating torn record {}: {:?}", index.entries() - 1, e);
+                torn_end = torn_end.max(last.offset.saturating_add(last.size));
+                index.truncate(index.entries() - 1);
+            }
+        }
+    }
+
+    let size = match index.entries() {
+        0 => log.start(),
+        entries => {
+            let last = index.read_at(entries - 1)?;
+            last.offset + last.size
+        }
+    };
+    log.restore(size)?;
+    log.clear_until(torn_end);
+
+    Ok(size)
 }
 
 #[cfg(test)]
@@ -96,14 +506,227 @@ mod tests {
     extern crate tempfile;
     use super::*;
     use std::fs::{self, File};
-    use std::io::Write;
+    use std::io::{Seek, SeekFrom, Write};
     use std::path::Path;
     use tempfile::tempdir;
 
     #[test]
     #[should_panic]
     fn test_invalid_create() {
-        Segment::new(Path::new("/invalid/dir/").to_path_buf(), 0, 100, 1000).unwrap();
+        Segment::new(
+            Path::new("/invalid/dir/").to_path_buf(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            1,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.flush().unwrap();
+
+        let mut r = Segment::open_read_only(
+            tmp_dir.join("00000000000000000001.log"),
+            tmp_dir.join("00000000000000000001.idx"),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        assert_eq!(r.offset, 1);
+        assert_eq!(r.entries(), 2);
+        assert_eq!(r.read_at(1).unwrap(), b"second-message");
+        r.verify().unwrap();
+
+        // never written to
+        assert!(!r.fit(1));
+        assert!(r.write(b"third", &mut Latency::default()).is_err());
+        let log = fs::read(tmp_dir.join("00000000000000000001.log")).unwrap();
+        assert_eq!(&log[40..53], b"first-message");
+        assert_eq!(&log[61..75], b"second-message");
+        assert_eq!(log[75], 0);
+        assert!(!tmp_dir.join("00000000000000000001.ftr").exists());
+    }
+
+    #[test]
+    fn test_seal() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let (log_path, index_path, footer_path) = (
+            tmp_dir.join("00000000000000000000.log"),
+            tmp_dir.join("00000000000000000000.idx"),
+            tmp_dir.join("00000000000000000000.ftr"),
+        );
+
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.seal().unwrap();
+        assert!(!s.fit(1));
+        assert!(s.write(b"third-message", &mut Latency::default()).is_err());
+
+        // trimmed down to the bytes written, and still readable
+        assert_eq!(fs::metadata(&log_path).unwrap().len(), 75);
+        assert_eq!(fs::metadata(&index_path).unwrap().len(), 64);
+        assert_eq!(s.read_at(1).unwrap(), b"second-message");
+        s.verify().unwrap();
+
+        let footer = Footer::read(&footer_path).unwrap().unwrap();
+        assert_eq!((footer.entries, footer.size), (2, 75));
+
+        // a valid footer is trusted, without scanning the index
+        Footer {
+            entries: 1,
+            size: 53,
+            index_crc: crc::crc32::checksum_castagnoli(
+                // the header and the first entry
+                &fs::read(&index_path).unwrap()[0..(3 * index::ENTRY_SIZE)],
+            ),
+        }
+        .write(&footer_path)
+        .unwrap();
+        let r = Segment::open_read_only(&log_path, &index_path, IndexLayout::default()).unwrap();
+        assert_eq!(r.entries(), 1);
+
+        // an invalid one triggers a rescan
+        Footer {
+            entries: 1,
+            size: 53,
+            index_crc: 0,
+        }
+        .write(&footer_path)
+        .unwrap();
+        let r = Segment::open_read_only(&log_path, &index_path, IndexLayout::default()).unwrap();
+        assert_eq!(r.entries(), 2);
+
+        // which catches a torn tail
+        fs::remove_file(&footer_path).unwrap();
+        let mut log = fs::OpenOptions::new().write(true).open(&log_path).unwrap();
+        log.set_len(52).unwrap();
+        log.flush().unwrap();
+        assert!(Segment::open_read_only(&log_path, &index_path, IndexLayout::default()).is_err());
+    }
+
+    #[test]
+    fn test_reopen() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let open = || {
+            Segment::new(
+                tmp_dir.clone(),
+                0,
+                100,
+                1000,
+                OversizedSegment::default(),
+                IndexLayout::default(),
+            )
+            .unwrap()
+        };
+
+        let mut s = open();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.flush().unwrap();
+        drop(s);
+
+        // recovered from the index
+        let mut s = open();
+        assert_eq!(s.entries(), 1);
+        assert_eq!(s.size(), 53);
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        assert_eq!(s.read_at(0).unwrap(), b"first-message");
+        s.seal().unwrap();
+        drop(s);
+
+        // restored from the footer, still sealed and trimmed, without touching the files
+        let modified = |extension| {
+            fs::metadata(tmp_dir.join(format!("00000000000000000000.{}", extension)))
+                .and_then(|metadata| metadata.modified())
+                .unwrap()
+        };
+        let (log_modified, index_modified) = (modified("log"), modified("idx"));
+        let mut s = open();
+        assert_eq!(modified("log"), log_modified);
+        assert_eq!(modified("idx"), index_modified);
+        assert!(s.is_sealed());
+        assert!(!s.fit(1));
+        assert_eq!(s.entries(), 2);
+        assert_eq!(s.read_at(1).unwrap(), b"second-message");
+        assert_eq!(
+            fs::metadata(tmp_dir.join("00000000000000000000.log"))
+                .unwrap()
+                .len(),
+            75
+        );
+    }
+
+    #[test]
+    fn test_reopen_torn() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let log_path = tmp_dir.join("00000000000000000000.log");
+        let open = || {
+            Segment::new(
+                tmp_dir.clone(),
+                0,
+                100,
+                1000,
+                OversizedSegment::default(),
+                IndexLayout::default(),
+            )
+            .unwrap()
+        };
+
+        let mut s = open();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.flush().unwrap();
+        drop(s);
+
+        // the second record only partially reached the disk
+        let mut log = fs::OpenOptions::new().write(true).open(&log_path).unwrap();
+        log.seek(SeekFrom::Start(67)).unwrap();
+        log.write_all(&[0; 8]).unwrap();
+        drop(log);
+
+        // truncated after the last intact record
+        let mut s = open();
+        assert_eq!(s.entries(), 1);
+        assert_eq!(s.size(), 53);
+        s.flush().unwrap();
+        assert!(fs::read(&log_path).unwrap()[53..75].iter().all(|b| *b == 0));
+
+        // and written over
+        s.write(b"third", &mut Latency::default()).unwrap();
+        s.flush().unwrap();
+        drop(s);
+
+        let s = open();
+        assert_eq!(s.entries(), 2);
+        assert_eq!(s.read_at(1).unwrap(), b"third");
+        s.verify().unwrap();
     }
 
     #[test]
@@ -113,7 +736,15 @@ mod tests {
         let expected_log_file = tmp_dir.clone().join("00000000000000000000.log");
         let expected_index_file = tmp_dir.clone().join("00000000000000000000.idx");
 
-        Segment::new(tmp_dir.clone(), 0, 10, 1000).unwrap();
+        Segment::new(
+            tmp_dir.clone(),
+            0,
+            10,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
 
         assert!(expected_log_file.as_path().exists());
         assert!(expected_index_file.as_path().exists());
@@ -127,41 +758,72 @@ mod tests {
 
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut s = Segment::new(tmp_dir.clone(), 0, 100, 100).unwrap();
-        s.write(b"2104").unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        s.write(b"2104", &mut Latency::default()).unwrap();
 
-        assert_eq!(
-            fs::read_to_string(expected_log_file).unwrap()[0..4],
-            String::from("2104")
-        );
+        // right after the record header
+        assert_eq!(fs::read(expected_log_file).unwrap()[40..44], *b"2104");
 
-        assert_eq!(
-            fs::read_to_string(expected_index_file).unwrap()[0..20],
-            String::from("00000000000000000004")
-        );
+        // right after the index header, pointing to the whole record after the log header
+        let index = fs::read(expected_index_file).unwrap();
+        assert_eq!(index[32..40], 32u64.to_le_bytes());
+        assert_eq!(index[40..48], 12u64.to_le_bytes());
     }
 
     #[test]
-    #[should_panic]
     fn it_fails_to_write_to_a_pre_existing_full_file() {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         let expected_file = tmp_dir.clone().join("00000000000000000000.log");
 
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
+        // a file that isn't a log is never written to
         let mut file = File::create(expected_file.clone()).unwrap();
-        file.write(b"initial-content-18").unwrap(); // occupies 18 bytes
+        file.write_all(b"initial-content-18").unwrap(); // occupies 18 bytes
+        let segment = |size| {
+            Segment::new(
+                tmp_dir.clone(),
+                0,
+                size,
+                1000,
+                OversizedSegment::default(),
+                IndexLayout::default(),
+            )
+        };
+        assert!(matches!(
+            segment(59),
+            Err(Error::Log(log::Error::Header(
+                header::Error::UnsupportedFormat
+            )))
+        ));
+        assert!(fs::read(&expected_file)
+            .unwrap()
+            .starts_with(b"initial-content-18"));
+        fs::remove_file(&expected_file).unwrap();
 
-        let mut s = Segment::new(tmp_dir.clone(), 0, 20, 1000).unwrap(); // set the limit to 20 bytes
-        s.write(b"1").unwrap(); // should be able to write 1 byte (total 19)
+        // the header and a first record occupy 50 bytes
+        let mut s = segment(59).unwrap(); // set the limit to 59 bytes
+        s.write(b"content-10", &mut Latency::default()).unwrap();
+        drop(s);
 
-        assert_eq!(
-            fs::read_to_string(expected_file).unwrap(),
-            String::from("initial-content-181")
-        );
+        let mut s = segment(59).unwrap();
+        s.write(b"1", &mut Latency::default()).unwrap(); // should be able to write 1 byte (total 59)
+        assert_eq!(s.read_at(1).unwrap(), b"1");
 
-        // should not be able to write another 16 bytes
-        s.write(b"this-should-error").unwrap();
+        // should not be able to write another 17 bytes
+        assert!(matches!(
+            s.write(b"this-should-error", &mut Latency::default()),
+            Err(Error::Log(log::Error::NoSpaceLeft))
+        ));
+        assert_eq!(s.entries(), 2);
     }
 
     #[test]
@@ -170,11 +832,44 @@ mod tests {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
-        let mut s = Segment::new(tmp_dir.clone(), 0, 20, 1000).unwrap();
-        s.write(b"this-has-17-bytes").unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            25,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        s.write(b"this-has-17-bytes", &mut Latency::default())
+            .unwrap();
+
+        // it already has 21 bytes out of 25, it won't fit another record
+        s.write(b"this-should-error", &mut Latency::default())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_write_index_full() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+
+        // room for a single entry
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            index_size_for(1, IndexLayout::default()),
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        assert!(s.write(b"second-message", &mut Latency::default()).is_err());
 
-        // it already has 17 bytes out of 20, it won't fit more than 3
-        s.write(b"this-should-error").unwrap();
+        // no record is left in the log without an entry
+        assert_eq!(s.size(), 53);
+        s.verify().unwrap();
     }
 
     #[test]
@@ -183,15 +878,39 @@ mod tests {
         fs::create_dir_all(tmp_dir.clone()).unwrap();
 
         // check index size
-        let mut s = Segment::new(tmp_dir.clone(), 0, 20, 10).unwrap();
-        assert!(!s.fit(1)); // false because the index needs at least 20 bytes for an entry
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            20,
+            10,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+        assert!(!s.fit(1)); // false because the index needs at least 32 bytes (header + entry)
 
         // check buffer size
-        let mut s = Segment::new(tmp_dir.clone(), 0, 20, 10).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            20,
+            10,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         assert!(!s.fit(100)); // false because of buffer size
 
         // check correct
-        let mut s = Segment::new(tmp_dir.clone(), 0, 100, 100).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            100,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
         assert!(s.fit(50)); // true because both buffer and index fit
     }
 
@@ -199,13 +918,168 @@ mod tests {
     fn test_read() {
         let tmp_dir = tempdir().unwrap().path().to_owned();
         fs::create_dir_all(tmp_dir.clone()).unwrap();
-        let mut s = Segment::new(tmp_dir.clone(), 0, 100, 1000).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
 
-        s.write(b"first-message").unwrap();
-        s.write(b"second-message").unwrap();
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
         s.flush().unwrap();
 
         assert_eq!(s.read_at(0).unwrap(), b"first-message");
         assert_eq!(s.read_at(1).unwrap(), b"second-message");
+        assert_eq!(s.locate(1).unwrap(), (61, 14)); // past the record header
+        assert_eq!(s.scan_log().unwrap(), vec![(32, 21), (53, 22)]);
+        assert!(s.locate(2).is_err());
+
+        // an entry smaller than the header of a record, e.g. a corrupt index
+        s.index.write(index::Entry::new(75, 3)).unwrap();
+        assert!(matches!(
+            s.locate(2),
+            Err(Error::Log(log::Error::InvalidIndex))
+        ));
+    }
+
+    #[test]
+    fn test_flush_until() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.write(b"third-message", &mut Latency::default()).unwrap();
+
+        s.flush_until(1).unwrap();
+        // both headers, 2 records and 2 entries
+        assert_eq!(s.io_stats().bytes_flushed, 32 + 43 + 32 + 32);
+        assert!(s.flush_until(3).is_err());
+
+        s.sync().unwrap();
+        assert_eq!(s.io_stats().bytes_flushed, 32 + 64 + 32 + 48);
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.verify().unwrap();
+
+        let violation = |entries: &[index::Entry]| {
+            let tmp_dir = tempdir().unwrap().path().to_owned();
+            fs::create_dir_all(tmp_dir.clone()).unwrap();
+            let mut s = Segment::new(
+                tmp_dir.clone(),
+                0,
+                100,
+                1000,
+                OversizedSegment::default(),
+                IndexLayout::default(),
+            )
+            .unwrap();
+            // two records of 13 bytes each, after the header of the log
+            s.log.write(b"01234").unwrap();
+            s.log.write(b"56789").unwrap();
+            for entry in entries {
+                s.index
+                    .write(index::Entry::new(entry.offset, entry.size))
+                    .unwrap();
+            }
+
+            match s.verify() {
+                Err(Error::Invariant(i, violation)) => (i, violation),
+                other => panic!("expected Error::Invariant, got {:?}", other),
+            }
+        };
+
+        use index::Entry;
+        assert_eq!(violation(&[Entry::new(32, 0)]), (0, Violation::ZeroSize));
+        assert_eq!(violation(&[Entry::new(37, 13)]), (0, Violation::Gap));
+        assert_eq!(
+            violation(&[Entry::new(32, 13), Entry::new(32, 13)]),
+            (1, Violation::NotIncreasing)
+        );
+        assert_eq!(
+            violation(&[Entry::new(32, 13), Entry::new(35, 13)]),
+            (1, Violation::Overlap)
+        );
+        assert_eq!(
+            violation(&[Entry::new(32, 13), Entry::new(45, 14)]),
+            (1, Violation::OutOfBounds)
+        );
+        assert_eq!(
+            violation(&[Entry::new(32, 13), Entry::new(45, 12)]),
+            (1, Violation::LengthMismatch)
+        );
+        assert_eq!(
+            violation(&[Entry::new(32, 13), Entry::new(45, usize::MAX)]),
+            (1, Violation::OutOfBounds)
+        );
+
+        // a flipped bit in the payload of the second record
+        let mut log = fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_dir.join("00000000000000000000.log"))
+            .unwrap();
+        log.seek(SeekFrom::Start(62)).unwrap();
+        log.write_all(b"X").unwrap();
+        match s.verify() {
+            Err(Error::Invariant(1, Violation::ChecksumMismatch)) => {}
+            other => panic!("expected a checksum mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_many() {
+        let tmp_dir = tempdir().unwrap().path().to_owned();
+        fs::create_dir_all(tmp_dir.clone()).unwrap();
+        let mut s = Segment::new(
+            tmp_dir.clone(),
+            0,
+            100,
+            1000,
+            OversizedSegment::default(),
+            IndexLayout::default(),
+        )
+        .unwrap();
+
+        s.write(b"first-message", &mut Latency::default()).unwrap();
+        s.write(b"second-message", &mut Latency::default()).unwrap();
+        s.write(b"third-message", &mut Latency::default()).unwrap();
+
+        assert_eq!(
+            s.read_many(1, 5).unwrap(),
+            vec![&b"second-message"[..], &b"third-message"[..]]
+        );
+        assert_eq!(
+            s.read_many(2, usize::MAX).unwrap(),
+            vec![&b"third-message"[..]]
+        );
     }
 }
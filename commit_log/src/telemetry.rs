@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+/// Operation
+///
+/// Internal maintenance operations reported when they get slow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// Sealing the active segment and creating the next one
+    Rotation,
+    /// Flushing segments to disk
+    Flush,
+}
+
+/// SlowOperation
+///
+/// Event emitted when an operation exceeds `Options::slow_operation_threshold`, to correlate
+/// latency spikes of the producers with the internal maintenance causing them.
+///
+/// Events are logged as warnings with the `commit_log::telemetry` target, and handed to
+/// `Options::on_slow_operation` when set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowOperation {
+    pub operation: Operation,
+    /// How long the operation took
+    pub duration: Duration,
+    /// Amount of bytes involved, sealed on rotations and flushed on flushes
+    pub bytes: usize,
+    /// What triggered the operation
+    pub cause: &'static str,
+}
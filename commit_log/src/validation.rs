@@ -0,0 +1,83 @@
+use std::str;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The payload is not valid UTF-8, starting at the given byte position
+    InvalidUtf8 { position: usize },
+    /// The payload is not a valid JSON document, at the given line and column
+    InvalidJson { line: usize, column: usize },
+}
+
+/// Validation
+///
+/// Checks applied to every payload before it's written to the log, so producer bugs are
+/// detected at the boundary rather than at consumption time.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Validation {
+    /// Accept any payload
+    #[default]
+    None,
+    /// Require payloads to be valid UTF-8
+    Utf8,
+    /// Require payloads to be valid JSON documents
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl Validation {
+    /// Validate the given payload
+    pub fn validate(self, buffer: &[u8]) -> Result<(), Error> {
+        match self {
+            Validation::None => Ok(()),
+            Validation::Utf8 => {
+                str::from_utf8(buffer)
+                    .map(|_| ())
+                    .map_err(|e| Error::InvalidUtf8 {
+                        position: e.valid_up_to(),
+                    })
+            }
+            #[cfg(feature = "json")]
+            Validation::Json => serde_json::from_slice::<serde::de::IgnoredAny>(buffer)
+                .map(|_| ())
+                .map_err(|e| Error::InvalidJson {
+                    line: e.line(),
+                    column: e.column(),
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_none() {
+        assert_eq!(Validation::None.validate(b"\xff\xfe"), Ok(()));
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(Validation::Utf8.validate("hello wörld".as_bytes()), Ok(()));
+        assert_eq!(
+            Validation::Utf8.validate(b"hello\xffworld"),
+            Err(Error::InvalidUtf8 { position: 5 })
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            Validation::Json.validate(br#"{"id":1,"tags":["a"]}"#),
+            Ok(())
+        );
+        assert_eq!(
+            Validation::Json.validate(b"{\"id\":1,\n\"tags\":[\"a\"}"),
+            Err(Error::InvalidJson {
+                line: 2,
+                column: 12
+            })
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use crate::StuckFlush;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Watchdog
+///
+/// A background thread watching over flushes, so a flush hanging on a dying disk is detected
+/// while it's still stuck, instead of silently stalling the writer.
+///
+/// Flushes are watched while a `Guard` returned by `arm` is alive. A flush exceeding the
+/// deadline is reported as an error through the `log` crate and marks the watchdog as
+/// degraded, or aborts the process, depending on the `StuckFlush` action.
+#[derive(Debug)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// When the flush being watched started, `None` when idle
+    armed: Mutex<Option<Instant>>,
+
+    /// Set once a flush exceeded the deadline, never cleared
+    degraded: AtomicBool,
+
+    /// Set when the watchdog is dropped
+    stop: AtomicBool,
+}
+
+/// Keeps the watchdog armed until dropped
+pub struct Guard {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    /// Spawn the watchdog thread
+    pub fn spawn(deadline: Duration, action: StuckFlush) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            armed: Mutex::new(None),
+            degraded: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        });
+
+        let watched = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("voik-watchdog".into())
+            .spawn(move || watch(&watched, deadline, action))?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Start watching an operation, until the returned guard is dropped
+    pub fn arm(&self) -> Guard {
+        *self.shared.armed.lock().unwrap() = Some(Instant::now());
+        Guard {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Return true if an operation ever exceeded the deadline
+    pub fn is_degraded(&self) -> bool {
+        self.shared.degraded.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        *self.shared.armed.lock().unwrap() = None;
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(shared: &Shared, deadline: Duration, action: StuckFlush) {
+    // check a few times per deadline, so a stuck flush is caught shortly after exceeding it
+    let interval = (deadline / 4).max(Duration::from_millis(1));
+
+    while !shared.stop.load(Ordering::Acquire) {
+        thread::park_timeout(interval);
+
+        let started = match *shared.armed.lock().unwrap() {
+            Some(started) => started,
+            None => continue,
+        };
+
+        let elapsed = started.elapsed();
+        if elapsed <= deadline || shared.degraded.load(Ordering::Acquire) {
+            continue;
+        }
+
+        log::error!(
+            "flush stuck for {:?}, exceeding the deadline of {:?}",
+            elapsed,
+            deadline
+        );
+        shared.degraded.store(true, Ordering::Release);
+
+        if action == StuckFlush::Abort {
+            std::process::abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stuck_operation() {
+        let w = Watchdog::spawn(Duration::from_millis(10), StuckFlush::Degrade).unwrap();
+
+        let guard = w.arm();
+        thread::sleep(Duration::from_millis(100));
+        drop(guard);
+
+        assert!(w.is_degraded());
+    }
+
+    #[test]
+    fn test_fast_operation() {
+        let w = Watchdog::spawn(Duration::from_secs(60), StuckFlush::Abort).unwrap();
+
+        drop(w.arm());
+        thread::sleep(Duration::from_millis(10));
+
+        assert!(!w.is_degraded());
+    }
+}
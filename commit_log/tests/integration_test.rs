@@ -18,8 +18,8 @@ fn test_commit_log_data_consistency_of_random_values() {
         NUMBER_OF_ELEMENTS_TO_INSERT,
         DATA_ITEM_SIZE,
         |random_value| {
-            write_crc.write(&random_value);
-            commit_log.write(&*random_value).unwrap();
+            write_crc.write(random_value);
+            commit_log.write(random_value).unwrap();
         },
     );
 
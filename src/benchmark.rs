@@ -1,36 +1,13 @@
 extern crate commit_log;
 extern crate dirs;
 
-use commit_log::Reader;
-use commit_log::Record;
 use commit_log::{CommitLog, Error};
 use std::time::SystemTime;
 
 fn loop_commit_log(clog: &CommitLog) -> Result<(), Error> {
-    let mut record = Record {
-        current_offset: 0,
-        segment_index: 1,
-    };
-    let reader = Reader { commit_log: &clog };
-    let mut segment_error = false;
-    loop {
-        match reader.read(&record) {
-            Ok(_) => {
-                segment_error = false;
-                record = Reader::next(&record);
-                //println!("{}", std::str::from_utf8(s).unwrap());
-            }
-            _ => {
-                if segment_error {
-                    //println!("error 2 {:?}", e);
-                    break;
-                } else {
-                    //println!("error 1 {:?}", e);
-                    segment_error = true;
-                    record = Reader::next_segment(&record);
-                }
-            }
-        }
+    for record in clog.iter() {
+        record?;
+        //println!("{}", std::str::from_utf8(record?).unwrap());
     }
     Ok(())
 }
@@ -63,7 +40,6 @@ fn main() -> Result<(), Error> {
     );
 
     // ########### Cold Read Performance Benchmark  ###########
-    // TODO implement a better way of READING sequencially, PLEASE
     // Read from first record, on the first segment (Horizon)
     loop_commit_log(&clog)?;
 
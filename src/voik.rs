@@ -1,3 +1,44 @@
+extern crate commit_log;
+
+use commit_log::{diff, CommitLog, Diff, Error};
+use std::env;
+use std::process;
+
+const USAGE: &str = "usage: voik diff <dir-a> <dir-b>";
+
+/// Compare the logs of both directories, opened read-only so they can be in use
+fn run_diff(left: &str, right: &str) -> Result<Diff, Error> {
+    let left = CommitLog::open_read_only(left)?;
+    let right = CommitLog::open_read_only(right)?;
+    diff(&left, &right)
+}
+
 fn main() {
-    println!("⚫️ voik says hi");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] => println!("⚫️ voik says hi"),
+        // exits like diff(1): 0 when identical, 1 when different, 2 on trouble
+        ["diff", left, right] => match run_diff(left, right) {
+            Ok(d) => {
+                match d.divergence {
+                    None => println!("identical, {} records", d.left_records),
+                    Some(position) => println!(
+                        "diverge at record {} ({} records vs {})",
+                        position, d.left_records, d.right_records
+                    ),
+                }
+                process::exit(if d.is_identical() { 0 } else { 1 });
+            }
+            Err(e) => {
+                eprintln!("voik diff: {:?}", e);
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
 }